@@ -0,0 +1,192 @@
+use crate::{
+    consts::{
+        ARROW_HEAD_SIZE, GRID_CELL_H, GRID_CELL_W, GRID_SIZE_X, GRID_SIZE_Y, SCREEN_H, SCREEN_W,
+        VECTOR_SCALE, VECTOR_WIDTH,
+    },
+    settings::{ArrowHead, MagnitudeSource, MagnitudeTarget, Setting},
+    visualizer::{Visualizer, VisualizerParams},
+};
+use log::info;
+use macroquad::prelude::*;
+use svg::node::element::{self, path::Data};
+
+type Point2<T> = [T; 2];
+
+#[derive(Clone, Default)]
+pub struct Arrow {
+    pub tail: Point2<f64>,
+    pub tip: Point2<f64>,
+    /// The two corners of the head on either side of the tip
+    pub barbs: [Point2<f64>; 2],
+}
+
+impl Arrow {
+    pub fn new(tail: Point2<f64>, angle: f64, length: f64, head_size: f64) -> Self {
+        let (dx, dy) = (angle.cos(), angle.sin());
+        let tip = [tail[0] + dx * length, tail[1] + dy * length];
+        let base = [tip[0] - dx * head_size, tip[1] - dy * head_size];
+        let (nx, ny) = (-dy * head_size / 2.0, dx * head_size / 2.0);
+
+        Self {
+            tail,
+            tip,
+            barbs: [[base[0] + nx, base[1] + ny], [base[0] - nx, base[1] - ny]],
+        }
+    }
+}
+
+/// An arrow per grid cell, pointing the same way as `LineSegments` but showing which way is
+/// forward and how strong the field is.
+pub struct Arrows {
+    arrows: Vec<Arrow>,
+    head: ArrowHead,
+}
+
+impl Arrows {
+    pub fn new(params: &VisualizerParams) -> Self {
+        let mut arrows = Self {
+            arrows: vec![Arrow::default(); GRID_SIZE_X * GRID_SIZE_Y],
+            head: params.settings.arrow_head,
+        };
+        arrows.place(params);
+
+        arrows
+    }
+
+    fn place(&mut self, params: &VisualizerParams) {
+        let settings = &params.settings;
+        let samples: Vec<(f64, f64)> = (0..GRID_SIZE_Y)
+            .flat_map(|y| (0..GRID_SIZE_X).map(move |x| (x, y)))
+            .map(|(x, y)| {
+                let p = [x as f64 * GRID_CELL_W, y as f64 * GRID_CELL_H];
+                let noise = params.noise_at(p);
+                let [dx, dy] = params.vector_at(p);
+                let magnitude = match settings.magnitude_source {
+                    MagnitudeSource::Constant => 1.0,
+                    MagnitudeSource::NoiseAmplitude => noise.abs(),
+                    MagnitudeSource::GradientNorm => {
+                        let [dx, dy] = params.gradient_at(p);
+                        dx.hypot(dy)
+                    }
+                    MagnitudeSource::FieldStrength => dx.hypot(dy),
+                };
+
+                (dy.atan2(dx), magnitude)
+            })
+            .collect();
+
+        // Scale so that the strongest point in view gets a full size arrow
+        let max_magnitude = samples
+            .iter()
+            .map(|(_, magnitude)| *magnitude)
+            .fold(0.0, f64::max);
+
+        for (i, (angle, magnitude)) in samples.into_iter().enumerate() {
+            let magnitude = if max_magnitude > 0.0 {
+                magnitude / max_magnitude
+            } else {
+                1.0
+            };
+            let (length, head_size) = match settings.magnitude_target {
+                MagnitudeTarget::Length => (VECTOR_SCALE * magnitude, ARROW_HEAD_SIZE),
+                MagnitudeTarget::HeadSize => (VECTOR_SCALE, ARROW_HEAD_SIZE * magnitude),
+            };
+            let tail = [
+                (i % GRID_SIZE_X) as f64 * GRID_CELL_W + GRID_CELL_W / 2.0,
+                (i / GRID_SIZE_X) as f64 * GRID_CELL_H + GRID_CELL_H / 2.0,
+            ];
+
+            self.arrows[i] = Arrow::new(tail, angle, length, head_size);
+        }
+
+        self.head = settings.arrow_head;
+    }
+}
+
+impl Visualizer for Arrows {
+    fn update(&mut self, params: &mut VisualizerParams) {
+        self.place(params);
+        // The other grid visualizers move through time once per row, so keep pace with them
+        params.z_offset += params.noise_speed * GRID_SIZE_Y as f64;
+    }
+
+    fn build_svg_document_from_state(&self) -> svg::Document {
+        let doc = svg::Document::new().set("viewBox", (0, 0, SCREEN_W, SCREEN_H));
+
+        let mut shafts = element::Group::new()
+            .set("fill", "none")
+            .set("stroke", "black")
+            .set("stroke-width", "0.3mm");
+
+        let mut heads = element::Group::new()
+            .set("fill", match self.head {
+                ArrowHead::Open => "none",
+                ArrowHead::Filled => "black",
+            })
+            .set("stroke", "black")
+            .set("stroke-width", "0.3mm");
+
+        info!("rendering {} arrows", self.arrows.len());
+
+        for arrow in self.arrows.iter() {
+            let [[x1, y1], [x2, y2]] = [arrow.tail, arrow.tip];
+            let shaft = element::Line::new()
+                .set("x1", x1)
+                .set("y1", y1)
+                .set("x2", x2)
+                .set("y2", y2);
+            shafts = shafts.add(shaft);
+
+            let [left, right] = arrow.barbs;
+            let data = Data::new()
+                .move_to((left[0], left[1]))
+                .line_to((x2, y2))
+                .line_to((right[0], right[1]));
+            let data = match self.head {
+                ArrowHead::Open => data,
+                ArrowHead::Filled => data.close(),
+            };
+            heads = heads.add(element::Path::new().set("d", data));
+        }
+
+        let bounding_rect = element::Rectangle::new()
+            .set("width", SCREEN_W)
+            .set("height", SCREEN_H)
+            .set("fill", "none")
+            .set("stroke", "black")
+            .set("stroke-width", "1mm");
+
+        doc.add(shafts).add(heads).add(bounding_rect)
+    }
+
+    fn render(&self) {
+        let width = VECTOR_WIDTH as f32;
+
+        for arrow in self.arrows.iter() {
+            let [[x1, y1], [x2, y2]] = [arrow.tail, arrow.tip];
+            let [[lx, ly], [rx, ry]] = arrow.barbs;
+            let [x1, y1, x2, y2] = [x1 as f32, y1 as f32, x2 as f32, y2 as f32];
+            let [lx, ly, rx, ry] = [lx as f32, ly as f32, rx as f32, ry as f32];
+
+            draw_line(x1, y1, x2, y2, width, WHITE);
+
+            match self.head {
+                ArrowHead::Open => {
+                    draw_line(lx, ly, x2, y2, width, WHITE);
+                    draw_line(rx, ry, x2, y2, width, WHITE);
+                }
+                ArrowHead::Filled => {
+                    draw_triangle(vec2(lx, ly), vec2(x2, y2), vec2(rx, ry), WHITE);
+                }
+            }
+        }
+    }
+
+    fn settings(&self) -> &'static [Setting] {
+        &[
+            Setting::ArrowHead,
+            Setting::MagnitudeSource,
+            Setting::MagnitudeTarget,
+        ]
+    }
+}
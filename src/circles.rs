@@ -49,16 +49,12 @@ impl Circles {
 
         for y in 0..GRID_SIZE_Y {
             for x in 0..GRID_SIZE_X {
-                let (x, y) = (x as f64, y as f64);
-
                 let location = [
-                    x * GRID_CELL_W + GRID_CELL_W / 2.0,
-                    y * GRID_CELL_H + GRID_CELL_H / 2.0,
+                    x as f64 * GRID_CELL_W + GRID_CELL_W / 2.0,
+                    y as f64 * GRID_CELL_H + GRID_CELL_H / 2.0,
                 ];
 
-                let radius = params.noise_fn.get([x, y, params.z_offset]) * scale;
-
-                circles.push(Circle::new(location, radius, scale));
+                circles.push(Circle::new(location, radius_at(params, x, y) * scale, scale));
             }
         }
 
@@ -66,22 +62,19 @@ impl Circles {
     }
 }
 
+/// How big the circle in the grid cell at column `x` and row `y` is before it's scaled, from the
+/// noise at the cell's corner.
+fn radius_at(params: &VisualizerParams, x: usize, y: usize) -> f64 {
+    params.noise_at([x as f64 * GRID_CELL_W, y as f64 * GRID_CELL_H]).abs()
+}
+
 impl Visualizer for Circles {
     fn update(&mut self, params: &mut VisualizerParams) {
-        let mut y_offset = 0.0 + params.base_y_offset;
         for y in 0..GRID_SIZE_Y {
-            let mut x_offset = 0.0 + params.base_x_offset;
             for x in 0..GRID_SIZE_X {
-                let radius = params
-                    .noise_fn
-                    .get([x_offset, y_offset, params.z_offset])
-                    .abs();
                 // TODO is just setting radius interesting enough?
-                self.circles[x + y * GRID_SIZE_X].set_radius(radius);
-
-                x_offset += params.noise_scale;
+                self.circles[x + y * GRID_SIZE_X].set_radius(radius_at(params, x, y));
             }
-            y_offset += params.noise_scale;
             // TODO is this really meant to happen per y?
             params.z_offset += params.noise_speed;
         }
@@ -124,4 +117,8 @@ impl Visualizer for Circles {
             draw_circle(x as f32, y as f32, circle.radius as f32, WHITE);
         }
     }
+
+    fn shows_noise_alone(&self) -> bool {
+        true
+    }
 }
@@ -0,0 +1,85 @@
+use crate::settings::Choice;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// Maps a value between 0 and 1 to a colour.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Colormap {
+    Viridis,
+    Magma,
+    Grayscale,
+    /// Blue through white to red. Zero always lands on white.
+    Diverging,
+}
+
+// Evenly spaced stops, linearly interpolated. Viridis and magma are sampled from matplotlib's
+// colormaps and the diverging one is ColorBrewer's RdBu.
+const VIRIDIS: &[u32] = &[
+    0x440154, 0x472c7a, 0x3b518b, 0x2c718e, 0x21908d, 0x27ad81, 0x5cc863, 0xaadc32, 0xfde725,
+];
+const MAGMA: &[u32] = &[
+    0x000004, 0x1c1044, 0x4f127b, 0x812581, 0xb5367a, 0xe55064, 0xfb8761, 0xfec287, 0xfcfdbf,
+];
+const GRAYSCALE: &[u32] = &[0x000000, 0xffffff];
+const DIVERGING: &[u32] = &[
+    0x2166ac, 0x4393c3, 0x92c5de, 0xd1e5f0, 0xf7f7f7, 0xfddbc7, 0xf4a582, 0xd6604d, 0xb2182b,
+];
+
+impl Colormap {
+    fn stops(self) -> &'static [u32] {
+        match self {
+            Colormap::Viridis => VIRIDIS,
+            Colormap::Magma => MAGMA,
+            Colormap::Grayscale => GRAYSCALE,
+            Colormap::Diverging => DIVERGING,
+        }
+    }
+
+    /// Whether values should be centred on zero rather than stretched to fill the colormap.
+    pub fn is_diverging(self) -> bool {
+        self == Colormap::Diverging
+    }
+
+    /// The colour at `t`, clamped to between 0 and 1, as red, green and blue from 0 to 1.
+    pub fn rgb(self, t: f64) -> [f64; 3] {
+        let stops = self.stops();
+        let position = t.clamp(0.0, 1.0) * (stops.len() - 1) as f64;
+        let i = (position.floor() as usize).min(stops.len() - 2);
+        let fraction = position - i as f64;
+        let [from, to] = [unpack(stops[i]), unpack(stops[i + 1])];
+
+        [0, 1, 2].map(|channel| from[channel] + (to[channel] - from[channel]) * fraction)
+    }
+
+    /// The colour at `t` as a CSS hex colour.
+    pub fn hex(self, t: f64) -> String {
+        let [r, g, b] = self.rgb(t).map(|channel| (channel * 255.0).round() as u8);
+
+        format!("#{r:02x}{g:02x}{b:02x}")
+    }
+}
+
+fn unpack(rgb: u32) -> [f64; 3] {
+    [16, 8, 0].map(|shift| ((rgb >> shift) & 0xff) as f64 / 255.0)
+}
+
+impl Choice for Colormap {
+    const ALL: &'static [Self] = &[
+        Colormap::Viridis,
+        Colormap::Magma,
+        Colormap::Grayscale,
+        Colormap::Diverging,
+    ];
+}
+
+impl fmt::Display for Colormap {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Colormap::Viridis => write!(f, "viridis"),
+            Colormap::Magma => write!(f, "magma"),
+            Colormap::Grayscale => write!(f, "grayscale"),
+            Colormap::Diverging => write!(f, "diverging"),
+        }
+    }
+}
@@ -4,12 +4,33 @@ pub const GRID_SIZE_X: usize = 96;
 pub const GRID_SIZE_Y: usize = 60;
 pub const VECTOR_SCALE: f64 = 20.0;
 pub const VECTOR_WIDTH: f64 = 2.0;
+pub const ARROW_HEAD_SIZE: f64 = 6.0;
+/// Distance in pixels between the samples used to estimate the gradient of the noise
+pub const GRADIENT_STEP: f64 = 1.0;
+/// Distance in pixels a streamline moves with each integration step
+pub const STREAMLINE_STEP: f64 = 4.0;
+/// Longest a streamline can get in pixels, counting both directions from its seed
+pub const STREAMLINE_MAX_LENGTH: f64 = 600.0;
+/// Streamlines are seeded at every Nth grid cell
+pub const STREAMLINE_SEED_SPACING: usize = 6;
+/// Evenly spaced streamlines stop at other lines long before this, it just keeps loops in check
+pub const EVENLY_SPACED_STREAMLINE_MAX_LENGTH: f64 = 4000.0;
+/// Distance in pixels a particle moves each frame
+pub const PARTICLE_SPEED: f64 = 2.0;
+/// Most points of faded and finished particle paths kept for exporting. The oldest paths go first.
+pub const PARTICLE_HISTORY_POINTS: usize = 200_000;
+/// Most faded and finished particle paths kept for exporting, since plots order every path
+pub const PARTICLE_HISTORY_PATHS: usize = 5_000;
+/// Distance in pixels between the noise samples that contours are traced through
+pub const CONTOUR_CELL_SIZE: f64 = 8.0;
 
 pub const DEFAULT_MOVE_SPEED: f64 = 0.001;
 pub const DEFAULT_NOISE_SCALE: f64 = 0.001;
 pub const DEFAULT_NOISE_SCALE_INCREMENT: f64 = DEFAULT_NOISE_SCALE * 0.01;
 pub const DEFAULT_NOISE_SPEED: f64 = 0.000005;
 pub const DEFAULT_NOISE_SPEED_INCREMENT: f64 = DEFAULT_NOISE_SPEED * 0.01;
+/// How many frames a looping animation takes to come back around
+pub const DEFAULT_LOOP_PERIOD: u32 = 120;
 
 pub const SCREEN_W: usize = 1920;
 pub const SCREEN_H: usize = 1080;
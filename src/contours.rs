@@ -0,0 +1,299 @@
+//! Iso-contours of the noise found with marching squares.
+
+use crate::{
+    consts::{CONTOUR_CELL_SIZE, GRID_SIZE_Y, SCREEN_H, SCREEN_W},
+    settings::Setting,
+    streamlines::{build_svg_document_from_polylines, draw_polyline},
+    visualizer::{Visualizer, VisualizerParams},
+};
+use macroquad::prelude::*;
+use std::collections::HashMap;
+
+type Point2<T> = [T; 2];
+
+/// A corner-to-corner edge of the sample grid. Neighbouring cells share edges, which is how
+/// segments from different cells get joined up.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+enum Edge {
+    /// From sample `(x, y)` to `(x + 1, y)`
+    Horizontal(usize, usize),
+    /// From sample `(x, y)` to `(x, y + 1)`
+    Vertical(usize, usize),
+}
+
+/// Noise sampled at the corners of a grid covering the screen.
+struct ScalarField {
+    columns: usize,
+    rows: usize,
+    values: Vec<f64>,
+}
+
+impl ScalarField {
+    fn sample(params: &VisualizerParams) -> Self {
+        let columns = (SCREEN_W as f64 / CONTOUR_CELL_SIZE).ceil() as usize + 1;
+        let rows = (SCREEN_H as f64 / CONTOUR_CELL_SIZE).ceil() as usize + 1;
+        let values = (0..rows)
+            .flat_map(|y| (0..columns).map(move |x| (x, y)))
+            .map(|(x, y)| params.noise_at(Self::position(x, y)))
+            .collect();
+
+        Self {
+            columns,
+            rows,
+            values,
+        }
+    }
+
+    fn position(x: usize, y: usize) -> Point2<f64> {
+        [x as f64 * CONTOUR_CELL_SIZE, y as f64 * CONTOUR_CELL_SIZE]
+    }
+
+    fn value(&self, x: usize, y: usize) -> f64 {
+        self.values[x + y * self.columns]
+    }
+
+    fn range(&self) -> (f64, f64) {
+        let min = self.values.iter().copied().fold(f64::INFINITY, f64::min);
+        let max = self.values.iter().copied().fold(f64::NEG_INFINITY, f64::max);
+
+        (min, max)
+    }
+
+    /// Where `level` crosses `edge`, found by interpolating between its corners.
+    fn crossing(&self, edge: Edge, level: f64) -> Point2<f64> {
+        let ((x0, y0), (x1, y1)) = match edge {
+            Edge::Horizontal(x, y) => ((x, y), (x + 1, y)),
+            Edge::Vertical(x, y) => ((x, y), (x, y + 1)),
+        };
+        let (a, b) = (self.value(x0, y0), self.value(x1, y1));
+        let t = if a != b { (level - a) / (b - a) } else { 0.5 };
+        let ([px0, py0], [px1, py1]) = (Self::position(x0, y0), Self::position(x1, y1));
+
+        [px0 + (px1 - px0) * t, py0 + (py1 - py0) * t]
+    }
+
+    /// The pieces of the contour at `level` within each cell, as pairs of edges.
+    fn segments(&self, level: f64) -> Vec<(Edge, Edge)> {
+        let mut segments = Vec::new();
+
+        for y in 0..self.rows - 1 {
+            for x in 0..self.columns - 1 {
+                let corners = [
+                    self.value(x, y),
+                    self.value(x + 1, y),
+                    self.value(x + 1, y + 1),
+                    self.value(x, y + 1),
+                ];
+                let case = corners
+                    .iter()
+                    .fold(0, |case, value| (case << 1) | (*value >= level) as u8);
+
+                let top = Edge::Horizontal(x, y);
+                let right = Edge::Vertical(x + 1, y);
+                let bottom = Edge::Horizontal(x, y + 1);
+                let left = Edge::Vertical(x, y);
+                // Saddles are split based on the average of the corners
+                let center_is_above = corners.iter().sum::<f64>() / 4.0 >= level;
+
+                // Bits from high to low are top left, top right, bottom right and bottom left
+                match case {
+                    0 | 15 => {}
+                    1 | 14 => segments.push((left, bottom)),
+                    2 | 13 => segments.push((bottom, right)),
+                    3 | 12 => segments.push((left, right)),
+                    4 | 11 => segments.push((top, right)),
+                    6 | 9 => segments.push((top, bottom)),
+                    7 | 8 => segments.push((left, top)),
+                    5 if center_is_above => segments.extend([(left, top), (bottom, right)]),
+                    5 => segments.extend([(top, right), (left, bottom)]),
+                    10 if center_is_above => segments.extend([(top, right), (left, bottom)]),
+                    10 => segments.extend([(left, top), (bottom, right)]),
+                    _ => unreachable!(),
+                }
+            }
+        }
+
+        segments
+    }
+
+    /// Join the segments of the contour at `level` into as few polylines as possible. Closed
+    /// contours end where they start.
+    fn contour_lines(&self, level: f64) -> Vec<Vec<Point2<f64>>> {
+        let segments = self.segments(level);
+        let mut segments_by_edge: HashMap<Edge, Vec<usize>> = HashMap::new();
+
+        for (i, (a, b)) in segments.iter().enumerate() {
+            segments_by_edge.entry(*a).or_default().push(i);
+            segments_by_edge.entry(*b).or_default().push(i);
+        }
+
+        let mut used = vec![false; segments.len()];
+        let mut lines = Vec::new();
+
+        // Start with the loose ends so that open contours are walked from one end to the other,
+        // then pick up whatever's left, which can only be closed loops
+        let mut loose_ends = segments_by_edge
+            .iter()
+            .filter(|(_, touching)| touching.len() == 1)
+            .map(|(edge, _)| *edge)
+            .collect::<Vec<_>>();
+        // Sorted so that the same noise always gives the same SVG
+        loose_ends.sort();
+        let starts = loose_ends
+            .into_iter()
+            .chain(segments.iter().map(|(a, _)| *a))
+            .collect::<Vec<_>>();
+
+        for start in starts {
+            let mut edges = vec![start];
+            let mut current = start;
+
+            while let Some(&i) = segments_by_edge[&current].iter().find(|i| !used[**i]) {
+                used[i] = true;
+                let (a, b) = segments[i];
+                current = if a == current { b } else { a };
+                edges.push(current);
+            }
+
+            if edges.len() > 1 {
+                lines.push(
+                    edges
+                        .into_iter()
+                        .map(|edge| self.crossing(edge, level))
+                        .collect(),
+                );
+            }
+        }
+
+        lines
+    }
+}
+
+/// Topographic style lines joining points where the noise has the same value.
+pub struct Contours {
+    contours: Vec<Vec<Point2<f64>>>,
+}
+
+impl Contours {
+    pub fn new(params: &VisualizerParams) -> Self {
+        let mut contours = Self {
+            contours: Vec::new(),
+        };
+        contours.trace(params);
+
+        contours
+    }
+
+    fn trace(&mut self, params: &VisualizerParams) {
+        let field = ScalarField::sample(params);
+        let levels = match params.settings.contour_levels.as_slice() {
+            [] => {
+                // Evenly spaced between, but not including, the lowest and highest values
+                let (min, max) = field.range();
+                let count = params.settings.contour_count;
+                let spacing = (max - min) / (count + 1) as f64;
+
+                (1..=count).map(|i| min + spacing * i as f64).collect()
+            }
+            levels => levels.to_vec(),
+        };
+
+        self.contours = levels
+            .into_iter()
+            .flat_map(|level| field.contour_lines(level))
+            .collect();
+    }
+}
+
+impl Visualizer for Contours {
+    fn update(&mut self, params: &mut VisualizerParams) {
+        self.trace(params);
+        // The grid visualizers move through time once per row, so keep pace with them
+        params.z_offset += params.noise_speed * GRID_SIZE_Y as f64;
+    }
+
+    fn build_svg_document_from_state(&self) -> svg::Document {
+        build_svg_document_from_polylines(&self.contours)
+    }
+
+    fn render(&self) {
+        for contour in self.contours.iter() {
+            draw_polyline(contour, WHITE);
+        }
+    }
+
+    fn shows_noise_alone(&self) -> bool {
+        true
+    }
+
+    fn settings(&self) -> &'static [Setting] {
+        &[Setting::ContourLevels]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A field from rows of values, top to bottom.
+    fn field(rows: &[&[f64]]) -> ScalarField {
+        ScalarField {
+            columns: rows[0].len(),
+            rows: rows.len(),
+            values: rows.concat(),
+        }
+    }
+
+    /// Points in grid cells rather than pixels, to compare against.
+    fn in_cells(lines: Vec<Vec<Point2<f64>>>) -> Vec<Vec<Point2<f64>>> {
+        lines
+            .into_iter()
+            .map(|line| {
+                line.into_iter()
+                    .map(|[x, y]| [x / CONTOUR_CELL_SIZE, y / CONTOUR_CELL_SIZE])
+                    .map(|[x, y]| [(x * 1e9).round() / 1e9, (y * 1e9).round() / 1e9])
+                    .collect()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn open_contours_run_from_border_to_border() {
+        let field = field(&[&[0.0, 1.0, 2.0], &[0.0, 1.0, 2.0], &[0.0, 1.0, 2.0]]);
+
+        assert_eq!(
+            in_cells(field.contour_lines(0.5)),
+            vec![vec![[0.5, 0.0], [0.5, 1.0], [0.5, 2.0]]]
+        );
+    }
+
+    #[test]
+    fn closed_contours_end_where_they_start() {
+        let field = field(&[&[0.0, 0.0, 0.0], &[0.0, 1.0, 0.0], &[0.0, 0.0, 0.0]]);
+        let lines = in_cells(field.contour_lines(0.5));
+
+        assert_eq!(lines.len(), 1);
+        assert_eq!(lines[0].len(), 5);
+        assert_eq!(lines[0][0], lines[0][4]);
+        for [x, y] in &lines[0] {
+            assert_eq!((x - 1.0).abs() + (y - 1.0).abs(), 0.5);
+        }
+    }
+
+    #[test]
+    fn saddles_are_split_by_the_middle_of_the_cell() {
+        let field = field(&[&[1.0, 0.0], &[0.0, 1.0]]);
+
+        // The middle is above 0.4, so the high corners are joined through it and the low ones
+        // are cut off
+        assert_eq!(
+            in_cells(field.contour_lines(0.4)),
+            vec![vec![[0.6, 0.0], [1.0, 0.4]], vec![[0.4, 1.0], [0.0, 0.6]]]
+        );
+        // and the other way around when it's below
+        assert_eq!(
+            in_cells(field.contour_lines(0.6)),
+            vec![vec![[0.4, 0.0], [0.0, 0.4]], vec![[0.6, 1.0], [1.0, 0.6]]]
+        );
+    }
+}
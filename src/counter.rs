@@ -17,6 +17,13 @@ impl Counter {
         self.count
     }
 
+    /// Set the current count. It must be between the minimum and maximum values.
+    pub fn set_count(&mut self, count: usize) {
+        assert!(self.min <= count && count <= self.max);
+
+        self.count = count;
+    }
+
     pub fn increment(&mut self) {
         match self.count.checked_add(1) {
             Some(new_count) if new_count > self.max => self.count = self.min,
@@ -0,0 +1,271 @@
+//! Evenly spaced streamlines, after Jobard and Lefer's "Creating Evenly-Spaced Streamlines of
+//! Arbitrary Density" (1997).
+//!
+//! Each new line is seeded `separation` away from a line that's already been placed, and stops
+//! as soon as it comes within `separation * test_distance` of any other line, or of itself.
+
+use crate::{
+    consts::{
+        EVENLY_SPACED_STREAMLINE_MAX_LENGTH, GRID_SIZE_Y, SCREEN_H, SCREEN_W, STREAMLINE_STEP,
+    },
+    field::{FieldMode, FlowFeature, Formula, SharedNoiseFn},
+    settings::{Setting, VisualizerSettings},
+    spatial_grid::SpatialGrid,
+    streamlines::{
+        build_svg_document_from_polylines, draw_polyline, is_on_screen, trace_streamline,
+        wrap_polyline, Integrator,
+    },
+    visualizer::{Visualizer, VisualizerParams},
+};
+use macroquad::prelude::*;
+use std::{collections::VecDeque, rc::Rc};
+
+type Point2<T> = [T; 2];
+
+pub struct EvenlySpacedStreamlines {
+    streamlines: Vec<Vec<Point2<f64>>>,
+    /// What the lines were last placed for, so that they're only placed again when it changes.
+    placed_for: Option<Placement>,
+}
+
+/// Everything that decides where the lines go.
+struct Placement {
+    noise_fn: SharedNoiseFn,
+    seed: u32,
+    field_mode: FieldMode,
+    formula: Option<Formula>,
+    features: Vec<FlowFeature>,
+    noise_weight: f64,
+    offsets: [f64; 3],
+    time: f64,
+    tiled: bool,
+    separation: f64,
+    test_distance: f64,
+}
+
+impl Placement {
+    fn new(params: &VisualizerParams) -> Self {
+        // Millimetres can come out smaller on big paper than the separation setting allows
+        let separation = params
+            .settings
+            .separation
+            .to_px(params.page.mm_per_px())
+            .max(VisualizerSettings::MIN_SEPARATION_PX);
+
+        Self {
+            noise_fn: params.noise_fn.clone(),
+            seed: params.seed,
+            field_mode: params.field_mode,
+            formula: params.formula.clone(),
+            features: params.features.clone(),
+            noise_weight: params.settings.noise_weight,
+            offsets: [params.base_x_offset, params.base_y_offset, params.noise_scale],
+            time: params.time(),
+            tiled: params.tiled,
+            separation,
+            test_distance: separation * params.settings.test_distance,
+        }
+    }
+}
+
+impl PartialEq for Placement {
+    fn eq(&self, other: &Self) -> bool {
+        // The noise is rebuilt whenever anything about it changes
+        Rc::ptr_eq(&self.noise_fn, &other.noise_fn)
+            && self.seed == other.seed
+            && self.field_mode == other.field_mode
+            && self.formula == other.formula
+            && self.features == other.features
+            && self.noise_weight == other.noise_weight
+            && self.offsets == other.offsets
+            && self.time == other.time
+            && self.tiled == other.tiled
+            && self.separation == other.separation
+            && self.test_distance == other.test_distance
+    }
+}
+
+/// The points of the line that's being traced, so that it can stop before it runs into itself.
+/// The newest points are held back for a while, since a line is always close to where it just was.
+struct TracedLine {
+    grid: SpatialGrid,
+    recent: VecDeque<Point2<f64>>,
+    lag: usize,
+}
+
+impl TracedLine {
+    fn new(separation: f64, lag: usize, tiled: bool) -> Self {
+        Self {
+            grid: SpatialGrid::new(separation).wrapping(tiled),
+            recent: VecDeque::new(),
+            lag,
+        }
+    }
+
+    /// Check that `p` is at least `distance` from the line's older points, and add it to the line
+    /// if it is.
+    fn extend_if_clear(&mut self, p: Point2<f64>, distance: f64) -> bool {
+        if !self.grid.is_clear(p, distance) {
+            return false;
+        }
+
+        self.recent.push_back(p);
+        if self.recent.len() > self.lag
+            && let Some(old) = self.recent.pop_front()
+        {
+            self.grid.insert(old);
+        }
+
+        true
+    }
+}
+
+impl EvenlySpacedStreamlines {
+    pub fn new(params: &VisualizerParams) -> Self {
+        let mut streamlines = Self {
+            streamlines: Vec::new(),
+            placed_for: None,
+        };
+        streamlines.place(params);
+
+        streamlines
+    }
+
+    fn place(&mut self, params: &VisualizerParams) {
+        let placement = Placement::new(params);
+        if self.placed_for.as_ref() == Some(&placement) {
+            return;
+        }
+
+        let separation = placement.separation;
+        let test_distance = placement.test_distance;
+        let step = STREAMLINE_STEP.min(separation / 2.0);
+        // The points a line has just passed through are always closer than `separation`, so they
+        // don't count when it's checked against itself
+        let lag = (separation / step).ceil() as usize + 1;
+        let mut grid = SpatialGrid::new(separation).wrapping(params.tiled);
+        let mut queue = VecDeque::new();
+
+        self.streamlines.clear();
+
+        // Lines can end early and leave holes that no neighbouring line reaches, so every point
+        // on a coarse grid is also tried as a seed once the queue runs dry
+        let fallback_spacing = separation * 4.0;
+        let mut fallback_seeds = (0..)
+            .map(|row| row as f64 * fallback_spacing + fallback_spacing / 2.0)
+            .take_while(|y| *y < SCREEN_H as f64)
+            .flat_map(|y| {
+                (0..)
+                    .map(|column| column as f64 * fallback_spacing + fallback_spacing / 2.0)
+                    .take_while(|x| *x < SCREEN_W as f64)
+                    .map(move |x| [x, y])
+            });
+
+        loop {
+            let seed = match queue.pop_front() {
+                Some(seed) => seed,
+                None => match fallback_seeds.next() {
+                    Some(seed) => seed,
+                    None => break,
+                },
+            };
+
+            if !grid.is_clear(seed, separation) {
+                continue;
+            }
+
+            let max_length = EVENLY_SPACED_STREAMLINE_MAX_LENGTH / 2.0;
+            let mut line = TracedLine::new(separation, lag, params.tiled);
+            let mut streamline = trace_streamline(
+                params,
+                Integrator::Rk4,
+                seed,
+                -step,
+                max_length,
+                &mut |p| grid.is_clear(p, test_distance) && line.extend_if_clear(p, test_distance),
+            );
+
+            // The forward half can't run into the backward half either, apart from the points
+            // right next to the seed that it starts from
+            let mut line = TracedLine::new(separation, lag, params.tiled);
+            for &p in streamline.iter().skip(lag) {
+                line.grid.insert(params.wrap(p));
+            }
+            let forward = trace_streamline(
+                params,
+                Integrator::Rk4,
+                seed,
+                step,
+                max_length,
+                &mut |p| grid.is_clear(p, test_distance) && line.extend_if_clear(p, test_distance),
+            );
+
+            streamline.reverse();
+            streamline.extend_from_slice(&forward[1..]);
+
+            // Lines shorter than the separation are just specks
+            if (streamline.len() - 1) as f64 * step < separation {
+                continue;
+            }
+
+            for &p in streamline.iter() {
+                grid.insert(params.wrap(p));
+            }
+
+            queue.extend(candidate_seeds(params, &streamline, separation));
+            if params.tiled {
+                self.streamlines.extend(wrap_polyline(&streamline));
+            } else {
+                self.streamlines.push(streamline);
+            }
+        }
+
+        self.placed_for = Some(placement);
+    }
+}
+
+/// Points `separation` away from either side of a streamline. Seeds off the edge of a tiled canvas
+/// wrap around onto it.
+fn candidate_seeds<'a>(
+    params: &'a VisualizerParams,
+    streamline: &'a [Point2<f64>],
+    separation: f64,
+) -> impl Iterator<Item = Point2<f64>> + 'a {
+    streamline.windows(3).flat_map(move |window| {
+        let [[x0, y0], [x1, y1], [x2, y2]] = [window[0], window[1], window[2]];
+        let (tx, ty) = (x2 - x0, y2 - y0);
+        let length = tx.hypot(ty).max(f64::EPSILON);
+        let (nx, ny) = (-ty / length * separation, tx / length * separation);
+
+        [[x1 + nx, y1 + ny], [x1 - nx, y1 - ny]]
+            .into_iter()
+            .map(|p| params.wrap(p))
+            .filter(|p| is_on_screen(*p))
+    })
+}
+
+impl Visualizer for EvenlySpacedStreamlines {
+    fn update(&mut self, params: &mut VisualizerParams) {
+        self.place(params);
+        // The grid visualizers move through time once per row, so keep pace with them
+        params.z_offset += params.noise_speed * GRID_SIZE_Y as f64;
+    }
+
+    fn build_svg_document_from_state(&self) -> svg::Document {
+        build_svg_document_from_polylines(&self.streamlines)
+    }
+
+    fn render(&self) {
+        for streamline in self.streamlines.iter() {
+            draw_polyline(streamline, WHITE);
+        }
+    }
+
+    fn settings(&self) -> &'static [Setting] {
+        &[
+            Setting::Separation,
+            Setting::SeparationUnit,
+            Setting::TestDistance,
+        ]
+    }
+}
@@ -0,0 +1,171 @@
+use crate::{
+    layers::{inkscape_layers, LayerOutput},
+    plot::{clip_paths, order_paths, pen_strokes, Travel},
+    plotter::{gcode, hpgl},
+    visualizer::{Visualizer, VisualizerParams},
+};
+use chrono::Local;
+use log::{info, warn};
+use std::{
+    error::Error,
+    fs,
+    path::{Path, PathBuf},
+};
+use svg::node::{element, Text};
+
+/// Find a filename for a new SVG in `base_path` that won't clobber an existing export.
+/// The seed is part of the filename so that the image can be recreated later.
+pub fn unused_svg_filepath(base_path: impl AsRef<Path>, seed: u32) -> PathBuf {
+    unused_filepath(base_path, seed, "svg")
+}
+
+/// Find a filename with the given extension in `base_path` that won't clobber an existing file.
+pub fn unused_filepath(base_path: impl AsRef<Path>, seed: u32, extension: &str) -> PathBuf {
+    let current_date = Local::today().format("%Y-%m-%d");
+    let filename = format!(
+        "{}-vector-field-visualization-seed-{}.{}",
+        &current_date, seed, extension
+    );
+    let mut filepath = base_path.as_ref().join(filename);
+
+    // I don't want to silently overwrite anything so I look for an unused filename,
+    // incrementing the counter until I find an unused number
+    // I could have also used a random string/number, I just like this better
+    if filepath.exists() {
+        let mut counter = 1;
+
+        while filepath.exists() {
+            if counter > 100 {
+                warn!(
+                    "unused_filepath counter has reached {}, you're not in an infinite loop are you?",
+                    counter
+                );
+            }
+
+            let _ = filepath.pop();
+            let filename = format!(
+                "{}-vector-field-visualization-seed-{}-{}.{}",
+                &current_date, seed, &counter, extension
+            );
+            filepath.push(filename);
+            counter += 1;
+        }
+    }
+
+    filepath
+}
+
+/// Export `visualizer`'s current state to an SVG at `svg_filepath`, clipped to the mask, split
+/// between pens, with its paths ordered for a plotter and laid out on the page from `params`.
+/// G-code and HPGL files of the same paths are written next to it if `params.plotter` asks for
+/// them.
+///
+/// When each pen gets a file of its own, they're numbered like `plot-pen-1.svg`.
+pub fn save_export(
+    visualizer: &dyn Visualizer,
+    params: &VisualizerParams,
+    svg_filepath: &Path,
+) -> Result<(), Box<dyn Error>> {
+    let mut document = visualizer.build_svg_document_from_state();
+    if let Some(mask) = &params.mask {
+        document = clip_paths(document, mask);
+    }
+
+    let layers = params.layers.split(document, params);
+    let mut travel = Travel::default();
+    let layers: Vec<svg::Document> = layers
+        .into_iter()
+        .map(|layer| {
+            // Each pen is plotted on its own, so each layer gets its own order
+            let (layer, layer_travel) = order_paths(layer);
+            travel.before += layer_travel.before;
+            travel.after += layer_travel.after;
+            layer
+        })
+        .collect();
+
+    let (scale, unit) = match params.page.placement() {
+        Some(placement) => (placement.scale, "mm"),
+        None => (1.0, "px"),
+    };
+    if travel.before > 0.0 {
+        info!(
+            "pen-up travel went from {:.0}{unit} to {:.0}{unit}",
+            travel.before * scale,
+            travel.after * scale
+        );
+    }
+
+    match params.layers.output {
+        LayerOutput::Files if layers.len() > 1 => {
+            for (i, layer) in layers.into_iter().enumerate() {
+                let pen = i + 1;
+                save_layers(vec![layer], pen, params, &pen_filepath(svg_filepath, pen))?;
+            }
+            Ok(())
+        }
+        _ => save_layers(layers, 1, params, svg_filepath),
+    }
+}
+
+/// Save the layers for pens counting up from `first_pen` to one SVG, in Inkscape layers if there's
+/// more than one, along with the plotter files `params.plotter` asks for.
+fn save_layers(
+    layers: Vec<svg::Document>,
+    first_pen: usize,
+    params: &VisualizerParams,
+    svg_filepath: &Path,
+) -> Result<(), Box<dyn Error>> {
+    if params.plotter.gcode || params.plotter.hpgl {
+        let polylines: Vec<_> = layers.iter().map(pen_strokes).collect();
+
+        if params.plotter.gcode {
+            let filepath = svg_filepath.with_extension("gcode");
+            let gcode = gcode(
+                &polylines,
+                first_pen,
+                &params.page,
+                &params.plotter,
+                &description(params),
+            );
+            fs::write(&filepath, gcode)?;
+            info!("G-code exported to {}", filepath.to_string_lossy());
+        }
+        if params.plotter.hpgl {
+            let filepath = svg_filepath.with_extension("hpgl");
+            fs::write(&filepath, hpgl(&polylines, first_pen, &params.page))?;
+            info!("HPGL exported to {}", filepath.to_string_lossy());
+        }
+    }
+
+    let mut layers: Vec<_> = layers.into_iter().map(|layer| params.page.fit(layer)).collect();
+    let document = match layers.len() {
+        1 => layers.remove(0),
+        _ => inkscape_layers(layers),
+    };
+    svg::save(svg_filepath, &with_metadata(document, params))?;
+
+    Ok(())
+}
+
+/// `plot.svg` becomes `plot-pen-2.svg` for pen 2.
+fn pen_filepath(path: &Path, pen: usize) -> PathBuf {
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    let extension = path.extension().unwrap_or_default().to_string_lossy();
+
+    path.with_file_name(format!("{stem}-pen-{pen}.{extension}"))
+}
+
+/// Record the parameters needed to reproduce an export in the SVG's `<desc>` element.
+pub fn with_metadata(document: svg::Document, params: &VisualizerParams) -> svg::Document {
+    document.add(element::Description::new().add(Text::new(description(params))))
+}
+
+/// The parameters needed to reproduce an export. Domain warping noise's offsets can be re-rolled
+/// away from the ones the seed gives, so its parameters are written out in full.
+fn description(params: &VisualizerParams) -> String {
+    match (&params.warp, &params.noise_graph) {
+        (Some(warp), None) => format!("seed={} {warp}", params.seed),
+        _ => format!("seed={}", params.seed),
+    }
+}
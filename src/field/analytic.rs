@@ -0,0 +1,169 @@
+use super::{AnalyticField, CompositeField, VectorField};
+use crate::settings::Choice;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// The shape of the flow around a `FlowFeature`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FlowKind {
+    /// Circles around its position, clockwise on screen for positive strengths
+    Vortex,
+    /// Pushes away from its position
+    Source,
+    /// Pulls in towards its position
+    Sink,
+    /// A source and sink pressed together, with the flow leaving along `angle`
+    Dipole,
+    /// Flows in along one axis and out along the other, turned by `angle`
+    Saddle,
+    /// The same everywhere, pointing along `angle`
+    Uniform,
+}
+
+impl Choice for FlowKind {
+    const ALL: &'static [Self] = &[
+        FlowKind::Vortex,
+        FlowKind::Source,
+        FlowKind::Sink,
+        FlowKind::Dipole,
+        FlowKind::Saddle,
+        FlowKind::Uniform,
+    ];
+}
+
+impl fmt::Display for FlowKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FlowKind::Vortex => write!(f, "vortex"),
+            FlowKind::Source => write!(f, "source"),
+            FlowKind::Sink => write!(f, "sink"),
+            FlowKind::Dipole => write!(f, "dipole"),
+            FlowKind::Saddle => write!(f, "saddle"),
+            FlowKind::Uniform => write!(f, "uniform"),
+        }
+    }
+}
+
+/// A classic analytic flow placed on the canvas.
+///
+/// Every kind except `Uniform` is softened inside `radius` so that it stays finite at its
+/// position, and is exactly `strength` long at `radius` pixels away from it.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct FlowFeature {
+    pub kind: FlowKind,
+    /// Where the feature sits, in pixels from the top left of the canvas
+    pub position: [f64; 2],
+    pub strength: f64,
+    /// How far out the feature reaches at full strength, in pixels
+    pub radius: f64,
+    /// Which way dipoles, saddles and uniform flows point, in degrees clockwise from the x axis
+    pub angle: f64,
+}
+
+impl FlowFeature {
+    pub const FIELD_NAMES: [&str; 3] = ["strength", "radius", "angle"];
+    const MIN_RADIUS: f64 = 1.0;
+
+    /// Whether the field at `field` (an index into `FIELD_NAMES`) changes this kind of flow.
+    /// Uniform flows have no radius, and only dipoles, saddles and uniform flows point anywhere.
+    pub fn has_field(&self, field: usize) -> bool {
+        match field {
+            0 => true,
+            1 => self.kind != FlowKind::Uniform,
+            2 => matches!(self.kind, FlowKind::Dipole | FlowKind::Saddle | FlowKind::Uniform),
+            _ => unreachable!(),
+        }
+    }
+
+    /// Format the field at `field` (an index into `FIELD_NAMES`) for display.
+    pub fn field_value(&self, field: usize) -> String {
+        match field {
+            0 => format!("{:.1}", self.strength),
+            1 => format!("{:.0} px", self.radius),
+            2 => format!("{:.0}°", self.angle),
+            _ => unreachable!(),
+        }
+    }
+
+    /// Nudge the field at `field` (an index into `FIELD_NAMES`) up or down by one step.
+    pub fn nudge(&mut self, field: usize, increase: bool) {
+        let sign = if increase { 1.0 } else { -1.0 };
+
+        match field {
+            // Negative strengths reverse the flow, so strength goes right through zero
+            0 => self.strength += sign * 0.1,
+            1 => {
+                self.radius = (self.radius * if increase { 1.1 } else { 1.0 / 1.1 })
+                    .max(Self::MIN_RADIUS)
+            }
+            2 => self.angle = (self.angle + sign * 15.0).rem_euclid(360.0),
+            _ => unreachable!(),
+        }
+    }
+
+    /// The flow at a point on the canvas.
+    pub fn sample(&self, x: f64, y: f64) -> [f64; 2] {
+        self.sample_offset([x - self.position[0], y - self.position[1]])
+    }
+
+    /// The flow at a point on a canvas that wraps around at `size`, measured from whichever copy
+    /// of the feature is nearest.
+    pub fn sample_wrapped(&self, x: f64, y: f64, [w, h]: [f64; 2]) -> [f64; 2] {
+        let [rx, ry] = [x - self.position[0], y - self.position[1]];
+        self.sample_offset([rx - w * (rx / w).round(), ry - h * (ry / h).round()])
+    }
+
+    /// The flow at `[rx, ry]` from the feature's position.
+    fn sample_offset(&self, [rx, ry]: [f64; 2]) -> [f64; 2] {
+        let (sin, cos) = self.angle.to_radians().sin_cos();
+        let r2 = rx * rx + ry * ry;
+        let s = self.radius;
+        // Falls off with distance like the ideal flows do, but levels off inside `radius`
+        let falloff = 2.0 * s / (r2 + s * s);
+
+        let [dx, dy] = match self.kind {
+            FlowKind::Vortex => [-ry * falloff, rx * falloff],
+            FlowKind::Source => [rx * falloff, ry * falloff],
+            FlowKind::Sink => [-rx * falloff, -ry * falloff],
+            FlowKind::Dipole => {
+                let along = cos * rx + sin * ry;
+                let scale = 4.0 * s * s / ((r2 + s * s) * (r2 + s * s));
+                [
+                    (2.0 * along * rx - cos * r2) * scale,
+                    (2.0 * along * ry - sin * r2) * scale,
+                ]
+            }
+            FlowKind::Saddle => {
+                // Work in the saddle's own frame, then turn the result back
+                let (u, v) = (cos * rx + sin * ry, -sin * rx + cos * ry);
+                let (du, dv) = (u * falloff, -v * falloff);
+                [cos * du - sin * dv, sin * du + cos * dv]
+            }
+            FlowKind::Uniform => [cos, sin],
+        };
+
+        [dx * self.strength, dy * self.strength]
+    }
+}
+
+impl Default for FlowFeature {
+    fn default() -> Self {
+        Self {
+            kind: FlowKind::Vortex,
+            position: [0.0, 0.0],
+            strength: 1.0,
+            radius: 200.0,
+            angle: 0.0,
+        }
+    }
+}
+
+/// The sum of every feature, sampled in canvas pixels.
+pub fn features_field(features: &[FlowFeature]) -> CompositeField {
+    features.iter().cloned().fold(CompositeField::new(), |field, feature| {
+        let feature_field = AnalyticField::new(move |x, y, _t| feature.sample(x, y));
+        field.with(1.0, Box::new(feature_field) as Box<dyn VectorField>)
+    })
+}
@@ -0,0 +1,749 @@
+use super::{SharedNoiseFn, VectorField};
+use crate::noise::{noise_fn_by_index, NOISE_FN_COUNT, NOISE_FN_IDENTIFIERS};
+use noise::NoiseFn;
+use serde::{Deserialize, Serialize};
+use std::{f64::consts, fmt, iter::Peekable, str::Chars, str::FromStr};
+
+/// A field written out by hand, either as an angle or as both components of a vector:
+///
+/// ```text
+/// angle = sin(x * 60) + noise(x, y, t)
+/// (dx, dy) = (-y, x)
+/// ```
+///
+/// A formula without a left hand side is an angle. `x` and `y` are in noise space and `t` is the
+/// same as `VisualizerParams::time`, which goes from 0 to 1 around the loop when time loops.
+/// `noise(x, y[, t])` samples the active noise, and every generator can be called by name, e.g.
+/// `perlin(x, y, t)`. Generators called by name loop along with the active noise.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct Formula {
+    source: String,
+    kind: FormulaKind,
+}
+
+#[derive(Debug, Clone)]
+enum FormulaKind {
+    /// The field points this many radians clockwise from the x axis with unit length
+    Angle(Expr),
+    /// The field is exactly `(dx, dy)`
+    Vector(Expr, Expr),
+}
+
+impl Formula {
+    /// The noise generators this formula calls by name, by index.
+    fn noise_fn_indices(&self) -> Vec<usize> {
+        let mut indices = Vec::new();
+        match &self.kind {
+            FormulaKind::Angle(expr) => expr.collect_noise_fn_indices(&mut indices),
+            FormulaKind::Vector(dx, dy) => {
+                dx.collect_noise_fn_indices(&mut indices);
+                dy.collect_noise_fn_indices(&mut indices);
+            }
+        }
+        indices.sort_unstable();
+        indices.dedup();
+
+        indices
+    }
+}
+
+impl FromStr for Formula {
+    type Err = String;
+
+    fn from_str(source: &str) -> Result<Self, Self::Err> {
+        let mut parser = Parser::new(source)?;
+        let kind = parser.formula()?;
+
+        Ok(Self {
+            source: source.trim().to_owned(),
+            kind,
+        })
+    }
+}
+
+impl TryFrom<String> for Formula {
+    type Error = String;
+
+    fn try_from(source: String) -> Result<Self, Self::Error> {
+        source.parse()
+    }
+}
+
+impl From<Formula> for String {
+    fn from(formula: Formula) -> Self {
+        formula.source
+    }
+}
+
+impl PartialEq for Formula {
+    fn eq(&self, other: &Self) -> bool {
+        self.source == other.source
+    }
+}
+
+impl fmt::Display for Formula {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.source)
+    }
+}
+
+/// A `Formula` ready to be sampled, along with every noise generator it calls.
+pub struct FormulaField {
+    formula: Formula,
+    noise: SharedNoiseFn,
+    noise_fns: Vec<Option<Box<dyn NoiseFn<f64, 3>>>>,
+}
+
+impl FormulaField {
+    /// Build the generators `formula` calls by name with `seed`, so that formulas are as
+    /// reproducible as the noise, looping every `loop_length` like the noise does.
+    pub fn new(
+        formula: Formula,
+        noise: SharedNoiseFn,
+        seed: u32,
+        loop_length: Option<f64>,
+    ) -> Self {
+        let mut noise_fns: Vec<_> = (0..NOISE_FN_COUNT).map(|_| None).collect();
+        for index in formula.noise_fn_indices() {
+            noise_fns[index] = Some(noise_fn_by_index(index, seed, None, None, loop_length));
+        }
+
+        Self {
+            formula,
+            noise,
+            noise_fns,
+        }
+    }
+}
+
+impl VectorField for FormulaField {
+    fn sample(&self, x: f64, y: f64, t: f64) -> [f64; 2] {
+        let env = Env {
+            x,
+            y,
+            t,
+            field: self,
+        };
+
+        let [dx, dy] = match &self.formula.kind {
+            FormulaKind::Angle(expr) => {
+                let angle = expr.eval(&env);
+                [angle.cos(), angle.sin()]
+            }
+            FormulaKind::Vector(dx, dy) => [dx.eval(&env), dy.eval(&env)],
+        };
+
+        // Formulas like `sqrt(-x)` or `1 / x` have no direction where they aren't a number
+        if dx.is_finite() && dy.is_finite() {
+            [dx, dy]
+        } else {
+            [0.0, 0.0]
+        }
+    }
+}
+
+/// Everything an expression can refer to while it's being evaluated.
+struct Env<'a> {
+    x: f64,
+    y: f64,
+    t: f64,
+    field: &'a FormulaField,
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Var {
+    X,
+    Y,
+    T,
+}
+
+#[derive(Debug, Clone, Copy)]
+enum BinaryOp {
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Rem,
+    Pow,
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Function {
+    Sin,
+    Cos,
+    Tan,
+    Asin,
+    Acos,
+    Atan,
+    Atan2,
+    Sinh,
+    Cosh,
+    Tanh,
+    Sqrt,
+    Abs,
+    Exp,
+    Ln,
+    Log2,
+    Log10,
+    Floor,
+    Ceil,
+    Round,
+    Fract,
+    Sign,
+    Min,
+    Max,
+    Pow,
+    Hypot,
+    Clamp,
+    Mix,
+    /// The active noise
+    Noise,
+    /// A noise generator by its index in `new_noise_fn_by_index`
+    NoiseFn(usize),
+}
+
+impl Function {
+    fn from_name(name: &str) -> Option<Self> {
+        let function = match name {
+            "sin" => Function::Sin,
+            "cos" => Function::Cos,
+            "tan" => Function::Tan,
+            "asin" => Function::Asin,
+            "acos" => Function::Acos,
+            "atan" => Function::Atan,
+            "atan2" => Function::Atan2,
+            "sinh" => Function::Sinh,
+            "cosh" => Function::Cosh,
+            "tanh" => Function::Tanh,
+            "sqrt" => Function::Sqrt,
+            "abs" => Function::Abs,
+            "exp" => Function::Exp,
+            "ln" => Function::Ln,
+            "log2" => Function::Log2,
+            "log10" => Function::Log10,
+            "floor" => Function::Floor,
+            "ceil" => Function::Ceil,
+            "round" => Function::Round,
+            "fract" => Function::Fract,
+            "sign" => Function::Sign,
+            "min" => Function::Min,
+            "max" => Function::Max,
+            "pow" => Function::Pow,
+            "hypot" => Function::Hypot,
+            "clamp" => Function::Clamp,
+            "mix" => Function::Mix,
+            "noise" => Function::Noise,
+            _ => Function::NoiseFn(NOISE_FN_IDENTIFIERS.iter().position(|&id| id == name)?),
+        };
+
+        Some(function)
+    }
+
+    /// The fewest and most arguments the function takes.
+    fn arity(self) -> (usize, usize) {
+        match self {
+            Function::Atan2 | Function::Min | Function::Max | Function::Pow | Function::Hypot => {
+                (2, 2)
+            }
+            Function::Clamp | Function::Mix => (3, 3),
+            // `t` can be left out of noise calls
+            Function::Noise | Function::NoiseFn(_) => (2, 3),
+            _ => (1, 1),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+enum Expr {
+    Number(f64),
+    Var(Var),
+    Neg(Box<Expr>),
+    Binary(BinaryOp, Box<Expr>, Box<Expr>),
+    Call(Function, Vec<Expr>),
+}
+
+impl Expr {
+    fn eval(&self, env: &Env) -> f64 {
+        match self {
+            Expr::Number(value) => *value,
+            Expr::Var(Var::X) => env.x,
+            Expr::Var(Var::Y) => env.y,
+            Expr::Var(Var::T) => env.t,
+            Expr::Neg(expr) => -expr.eval(env),
+            Expr::Binary(op, lhs, rhs) => {
+                let (lhs, rhs) = (lhs.eval(env), rhs.eval(env));
+                match op {
+                    BinaryOp::Add => lhs + rhs,
+                    BinaryOp::Sub => lhs - rhs,
+                    BinaryOp::Mul => lhs * rhs,
+                    BinaryOp::Div => lhs / rhs,
+                    BinaryOp::Rem => lhs.rem_euclid(rhs),
+                    BinaryOp::Pow => lhs.powf(rhs),
+                }
+            }
+            Expr::Call(function, args) => {
+                let args: Vec<f64> = args.iter().map(|arg| arg.eval(env)).collect();
+                call(*function, &args, env)
+            }
+        }
+    }
+
+    fn collect_noise_fn_indices(&self, indices: &mut Vec<usize>) {
+        match self {
+            Expr::Number(_) | Expr::Var(_) => {}
+            Expr::Neg(expr) => expr.collect_noise_fn_indices(indices),
+            Expr::Binary(_, lhs, rhs) => {
+                lhs.collect_noise_fn_indices(indices);
+                rhs.collect_noise_fn_indices(indices);
+            }
+            Expr::Call(function, args) => {
+                if let Function::NoiseFn(index) = function {
+                    indices.push(*index);
+                }
+                for arg in args {
+                    arg.collect_noise_fn_indices(indices);
+                }
+            }
+        }
+    }
+}
+
+fn call(function: Function, args: &[f64], env: &Env) -> f64 {
+    let a = args[0];
+    match function {
+        Function::Sin => a.sin(),
+        Function::Cos => a.cos(),
+        Function::Tan => a.tan(),
+        Function::Asin => a.asin(),
+        Function::Acos => a.acos(),
+        Function::Atan => a.atan(),
+        Function::Atan2 => a.atan2(args[1]),
+        Function::Sinh => a.sinh(),
+        Function::Cosh => a.cosh(),
+        Function::Tanh => a.tanh(),
+        Function::Sqrt => a.sqrt(),
+        Function::Abs => a.abs(),
+        Function::Exp => a.exp(),
+        Function::Ln => a.ln(),
+        Function::Log2 => a.log2(),
+        Function::Log10 => a.log10(),
+        Function::Floor => a.floor(),
+        Function::Ceil => a.ceil(),
+        Function::Round => a.round(),
+        Function::Fract => a - a.floor(),
+        Function::Sign => a.signum(),
+        Function::Min => a.min(args[1]),
+        Function::Max => a.max(args[1]),
+        Function::Pow => a.powf(args[1]),
+        Function::Hypot => a.hypot(args[1]),
+        Function::Clamp => a.clamp(args[1].min(args[2]), args[1].max(args[2])),
+        Function::Mix => a + (args[1] - a) * args[2],
+        Function::Noise | Function::NoiseFn(_) => {
+            let point = [a, args[1], args.get(2).copied().unwrap_or(env.t)];
+            match function {
+                Function::NoiseFn(index) => env.field.noise_fns[index]
+                    .as_ref()
+                    .expect("noise generators are built for every call in the formula")
+                    .get(point),
+                _ => env.field.noise.get(point),
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Number(f64),
+    Ident(String),
+    Op(char),
+}
+
+impl fmt::Display for Token {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Token::Number(value) => write!(f, "`{value}`"),
+            Token::Ident(name) => write!(f, "`{name}`"),
+            Token::Op(op) => write!(f, "`{op}`"),
+        }
+    }
+}
+
+fn tokenize(source: &str) -> Result<Vec<Token>, String> {
+    let mut tokens = Vec::new();
+    let mut chars = source.chars().peekable();
+
+    while let Some(&c) = chars.peek() {
+        if c.is_whitespace() {
+            chars.next();
+        } else if c.is_ascii_digit() || c == '.' {
+            tokens.push(Token::Number(number(&mut chars)?));
+        } else if c.is_alphabetic() || c == '_' {
+            let mut name = String::new();
+            while let Some(&c) = chars.peek() {
+                if !(c.is_alphanumeric() || c == '_') {
+                    break;
+                }
+                name.push(c);
+                chars.next();
+            }
+            tokens.push(Token::Ident(name));
+        } else if "+-*/%^(),=".contains(c) {
+            tokens.push(Token::Op(c));
+            chars.next();
+        } else {
+            return Err(format!("unexpected character `{c}` in formula"));
+        }
+    }
+
+    Ok(tokens)
+}
+
+fn number(chars: &mut Peekable<Chars>) -> Result<f64, String> {
+    let mut text = String::new();
+    while let Some(&c) = chars.peek() {
+        let is_exponent_sign =
+            (c == '+' || c == '-') && text.ends_with(['e', 'E']);
+        if !(c.is_ascii_digit() || c == '.' || c == 'e' || c == 'E' || is_exponent_sign) {
+            break;
+        }
+        text.push(c);
+        chars.next();
+    }
+
+    text.parse()
+        .map_err(|_| format!("`{text}` isn't a number"))
+}
+
+/// How deeply brackets, calls and unary minuses can be nested, so that a long enough formula
+/// can't overflow the stack.
+const MAX_NESTING: usize = 200;
+
+/// A recursive descent parser over the formula's tokens. From loosest to tightest binding:
+/// `+ -`, `* / %`, unary `-`, then `^`, which groups to the right.
+struct Parser {
+    tokens: Vec<Token>,
+    position: usize,
+    /// How many `unary`s deep the parser is, which every nested bracket, call and minus goes
+    /// through
+    depth: usize,
+}
+
+impl Parser {
+    fn new(source: &str) -> Result<Self, String> {
+        Ok(Self {
+            tokens: tokenize(source)?,
+            position: 0,
+            depth: 0,
+        })
+    }
+
+    fn formula(&mut self) -> Result<FormulaKind, String> {
+        let kind = if self.next_is_ident("angle") && self.peek_at(1) == Some(&Token::Op('=')) {
+            self.position += 2;
+            FormulaKind::Angle(self.expr()?)
+        } else if self.peek() == Some(&Token::Op('('))
+            && self.peek_at(1) == Some(&Token::Ident("dx".to_owned()))
+        {
+            for expected in ["(", "dx", ",", "dy", ")", "=", "("] {
+                self.expect(expected)?;
+            }
+            let dx = self.expr()?;
+            self.expect(",")?;
+            let dy = self.expr()?;
+            self.expect(")")?;
+            FormulaKind::Vector(dx, dy)
+        } else {
+            FormulaKind::Angle(self.expr()?)
+        };
+
+        match self.peek() {
+            None => Ok(kind),
+            Some(token) => Err(format!("unexpected {token} after the end of the formula")),
+        }
+    }
+
+    fn expr(&mut self) -> Result<Expr, String> {
+        let mut lhs = self.term()?;
+        loop {
+            let op = match self.peek() {
+                Some(Token::Op('+')) => BinaryOp::Add,
+                Some(Token::Op('-')) => BinaryOp::Sub,
+                _ => return Ok(lhs),
+            };
+            self.position += 1;
+            lhs = Expr::Binary(op, Box::new(lhs), Box::new(self.term()?));
+        }
+    }
+
+    fn term(&mut self) -> Result<Expr, String> {
+        let mut lhs = self.unary()?;
+        loop {
+            let op = match self.peek() {
+                Some(Token::Op('*')) => BinaryOp::Mul,
+                Some(Token::Op('/')) => BinaryOp::Div,
+                Some(Token::Op('%')) => BinaryOp::Rem,
+                _ => return Ok(lhs),
+            };
+            self.position += 1;
+            lhs = Expr::Binary(op, Box::new(lhs), Box::new(self.unary()?));
+        }
+    }
+
+    fn unary(&mut self) -> Result<Expr, String> {
+        if self.depth >= MAX_NESTING {
+            return Err(format!("formula is nested more than {MAX_NESTING} deep"));
+        }
+        self.depth += 1;
+
+        let expr = if self.peek() == Some(&Token::Op('-')) {
+            self.position += 1;
+            Expr::Neg(Box::new(self.unary()?))
+        } else {
+            self.power()?
+        };
+
+        self.depth -= 1;
+        Ok(expr)
+    }
+
+    fn power(&mut self) -> Result<Expr, String> {
+        let base = self.atom()?;
+        if self.peek() != Some(&Token::Op('^')) {
+            return Ok(base);
+        }
+        self.position += 1;
+
+        // `unary` rather than `power` so that `2^-x` works
+        Ok(Expr::Binary(
+            BinaryOp::Pow,
+            Box::new(base),
+            Box::new(self.unary()?),
+        ))
+    }
+
+    fn atom(&mut self) -> Result<Expr, String> {
+        let token = self
+            .tokens
+            .get(self.position)
+            .cloned()
+            .ok_or("formula ended early")?;
+        self.position += 1;
+
+        match token {
+            Token::Number(value) => Ok(Expr::Number(value)),
+            Token::Op('(') => {
+                let expr = self.expr()?;
+                self.expect(")")?;
+                Ok(expr)
+            }
+            Token::Ident(name) if self.peek() == Some(&Token::Op('(')) => self.call(&name),
+            Token::Ident(name) => match name.as_str() {
+                "x" => Ok(Expr::Var(Var::X)),
+                "y" => Ok(Expr::Var(Var::Y)),
+                "t" => Ok(Expr::Var(Var::T)),
+                "pi" => Ok(Expr::Number(consts::PI)),
+                "tau" => Ok(Expr::Number(consts::TAU)),
+                "e" => Ok(Expr::Number(consts::E)),
+                _ => Err(format!("unknown variable `{name}`")),
+            },
+            Token::Op(_) => Err(format!("unexpected {token}")),
+        }
+    }
+
+    fn call(&mut self, name: &str) -> Result<Expr, String> {
+        let function =
+            Function::from_name(name).ok_or_else(|| format!("unknown function `{name}`"))?;
+        self.expect("(")?;
+
+        let mut args = Vec::new();
+        if self.peek() != Some(&Token::Op(')')) {
+            args.push(self.expr()?);
+            while self.peek() == Some(&Token::Op(',')) {
+                self.position += 1;
+                args.push(self.expr()?);
+            }
+        }
+        self.expect(")")?;
+
+        let (min, max) = function.arity();
+        if args.len() < min || args.len() > max {
+            let expected = match (min, max) {
+                (1, 1) => "1 argument".to_owned(),
+                _ if min == max => format!("{min} arguments"),
+                _ => format!("{min} to {max} arguments"),
+            };
+            return Err(format!(
+                "`{name}` takes {expected} but was given {}",
+                args.len()
+            ));
+        }
+
+        Ok(Expr::Call(function, args))
+    }
+
+    fn peek(&self) -> Option<&Token> {
+        self.peek_at(0)
+    }
+
+    fn peek_at(&self, offset: usize) -> Option<&Token> {
+        self.tokens.get(self.position + offset)
+    }
+
+    fn next_is_ident(&self, name: &str) -> bool {
+        matches!(self.peek(), Some(Token::Ident(ident)) if ident == name)
+    }
+
+    /// Skip over `expected`, which is either a single operator or an identifier.
+    fn expect(&mut self, expected: &str) -> Result<(), String> {
+        let matches = match self.peek() {
+            Some(Token::Op(op)) => expected.len() == 1 && expected.starts_with(*op),
+            Some(Token::Ident(name)) => name == expected,
+            _ => false,
+        };
+        if !matches {
+            return Err(match self.peek() {
+                Some(token) => format!("expected `{expected}` but found {token}"),
+                None => format!("expected `{expected}` but the formula ended"),
+            });
+        }
+        self.position += 1;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::rc::Rc;
+
+    fn field(source: &str) -> FormulaField {
+        let formula = source.parse().unwrap();
+        FormulaField::new(formula, Rc::new(noise::Constant::new(0.5)), 0, None)
+    }
+
+    /// Evaluate an angle formula at `(x, y, t)`.
+    fn eval(source: &str, [x, y, t]: [f64; 3]) -> f64 {
+        let field = field(source);
+        let FormulaKind::Angle(expr) = &field.formula.kind else {
+            panic!("{source} isn't an angle");
+        };
+
+        expr.eval(&Env {
+            x,
+            y,
+            t,
+            field: &field,
+        })
+    }
+
+    fn error(source: &str) -> String {
+        source.parse::<Formula>().unwrap_err()
+    }
+
+    #[test]
+    fn precedence() {
+        assert_eq!(eval("1 + 2 * 3", [0.0; 3]), 7.0);
+        assert_eq!(eval("(1 + 2) * 3", [0.0; 3]), 9.0);
+        assert_eq!(eval("10 - 4 - 3", [0.0; 3]), 3.0);
+        assert_eq!(eval("12 / 3 / 2", [0.0; 3]), 2.0);
+        assert_eq!(eval("2 + 7 % 3", [0.0; 3]), 3.0);
+        assert_eq!(eval("2 * 3 ^ 2", [0.0; 3]), 18.0);
+        assert_eq!(eval("2 ^ 3 ^ 2", [0.0; 3]), 512.0);
+    }
+
+    #[test]
+    fn unary_minus() {
+        assert_eq!(eval("-2 ^ 2", [0.0; 3]), -4.0);
+        assert_eq!(eval("2 ^ -1", [0.0; 3]), 0.5);
+        assert_eq!(eval("--x", [3.0, 0.0, 0.0]), 3.0);
+        assert_eq!(eval("1 - -x", [3.0, 0.0, 0.0]), 4.0);
+        assert_eq!(eval("-x * 2", [3.0, 0.0, 0.0]), -6.0);
+        assert_eq!(eval("-7 % 3", [0.0; 3]), 2.0);
+    }
+
+    #[test]
+    fn variables_constants_and_calls() {
+        assert_eq!(eval("x + 10 * y + 100 * t", [1.0, 2.0, 3.0]), 321.0);
+        assert_eq!(eval("max(x, y) - min(x, y)", [1.0, 4.0, 0.0]), 3.0);
+        assert_eq!(eval("clamp(x, 1, 0)", [2.0, 0.0, 0.0]), 1.0);
+        assert_eq!(eval("noise(x, y)", [1.0, 2.0, 0.0]), 0.5);
+        assert_eq!(eval("tau / pi", [0.0; 3]), 2.0);
+        assert_eq!(eval("1.5e1 + 2E-1", [0.0; 3]), 15.2);
+    }
+
+    #[test]
+    fn forms() {
+        let angle = field("angle = x");
+        let bare = field("x");
+        for [x, y] in [[0.0, 0.0], [1.0, 2.0], [-0.5, 3.0]] {
+            assert_eq!(angle.sample(x, y, 0.0), [x.cos(), x.sin()]);
+            assert_eq!(bare.sample(x, y, 0.0), [x.cos(), x.sin()]);
+        }
+
+        let vector = field("(dx, dy) = (-y, x * 2)");
+        assert_eq!(vector.sample(1.0, 2.0, 0.0), [-2.0, 2.0]);
+    }
+
+    #[test]
+    fn results_that_arent_numbers_have_no_direction() {
+        assert_eq!(field("(dx, dy) = (1 / x, 0)").sample(0.0, 0.0, 0.0), [0.0, 0.0]);
+        assert_eq!(field("sqrt(-1 - x)").sample(0.0, 0.0, 0.0), [0.0, 0.0]);
+        assert_eq!(field("(dx, dy) = (ln(x), 1)").sample(0.0, 0.0, 0.0), [0.0, 0.0]);
+    }
+
+    #[test]
+    fn errors() {
+        assert_eq!(error("x $ y"), "unexpected character `$` in formula");
+        assert_eq!(error("1.2.3"), "`1.2.3` isn't a number");
+        assert_eq!(error("z"), "unknown variable `z`");
+        assert_eq!(error("foo(x)"), "unknown function `foo`");
+        assert_eq!(error("sin(x"), "expected `)` but the formula ended");
+        assert_eq!(error("x y"), "unexpected `y` after the end of the formula");
+        assert_eq!(error("x +"), "formula ended early");
+        assert_eq!(error("* x"), "unexpected `*`");
+        assert_eq!(error("atan2(x)"), "`atan2` takes 2 arguments but was given 1");
+        assert_eq!(error("sin(x, y)"), "`sin` takes 1 argument but was given 2");
+        assert_eq!(error("noise(x)"), "`noise` takes 2 to 3 arguments but was given 1");
+        assert_eq!(error("(dx, dy) = (x)"), "expected `,` but found `)`");
+        assert_eq!(error("(dx, y) = (x, y)"), "expected `dy` but found `y`");
+    }
+
+    #[test]
+    fn deep_nesting_is_an_error() {
+        let depth = 5_000;
+        let brackets = format!("{}x{}", "(".repeat(depth), ")".repeat(depth));
+        let minuses = format!("{}x", "-".repeat(depth));
+        let calls = format!("{}x{}", "sin(".repeat(depth), ")".repeat(depth));
+
+        for source in [brackets, minuses, calls] {
+            assert_eq!(error(&source), format!("formula is nested more than {MAX_NESTING} deep"));
+        }
+
+        let nested = format!("{}x{}", "(".repeat(150), ")".repeat(150));
+        assert!(nested.parse::<Formula>().is_ok());
+    }
+
+    #[test]
+    fn display_parses_back() {
+        let sources = [
+            "angle = sin(x * 60) + noise(x, y, t)",
+            "(dx, dy) = (-y, x)",
+            "  cos(perlin(x, y) * tau) ^ -2  ",
+            "atan2(y - 0.5, x - 0.5) + pi / 2",
+        ];
+
+        for source in sources {
+            let formula: Formula = source.parse().unwrap();
+            let reparsed: Formula = formula.to_string().parse().unwrap();
+            assert_eq!(reparsed, formula);
+
+            let [original, reparsed] = [formula, reparsed]
+                .map(|formula| FormulaField::new(formula, Rc::new(noise::Constant::new(0.5)), 7, None));
+            for [x, y, t] in [[0.1, 0.2, 0.3], [1.5, -2.0, 0.0]] {
+                assert_eq!(original.sample(x, y, t), reparsed.sample(x, y, t));
+            }
+        }
+    }
+}
@@ -0,0 +1,224 @@
+mod analytic;
+mod formula;
+
+pub use analytic::{features_field, FlowFeature, FlowKind};
+pub use formula::{Formula, FormulaField};
+
+use crate::{
+    consts::{GRID_CELL_H, GRID_CELL_W},
+    settings::Choice,
+};
+use clap::ValueEnum;
+use noise::NoiseFn;
+use serde::{Deserialize, Serialize};
+use std::{f64::consts::TAU, fmt, rc::Rc};
+
+/// A scalar noise that can be shared between `VisualizerParams` and the vector field built from
+/// it.
+pub type SharedNoiseFn = Rc<dyn NoiseFn<f64, 3>>;
+
+/// Distance in noise space between the samples used to estimate partial derivatives
+const EPSILON: f64 = 0.0001;
+
+/// Something that gives a direction and strength at every point. Visualizers only see the field
+/// through this, so any field source works with all of them.
+pub trait VectorField {
+    /// The field at `(x, y)` in noise space at time `t`, as `[dx, dy]`.
+    fn sample(&self, x: f64, y: f64, t: f64) -> [f64; 2];
+}
+
+/// The rate of change of `noise` at `p` in noise space, found with central differences.
+///
+/// Noise space squashes grid cells, which are wider than they are tall, into squares. The
+/// samples are taken the same distance apart on screen both ways, so that the gradient is
+/// at right angles to the lines of equal noise on screen and the curl has no sinks or sources
+/// there. Both are measured per unit of noise space down the screen.
+pub fn gradient<N>(noise: &N, [x, y, z]: [f64; 3]) -> [f64; 2]
+where
+    N: NoiseFn<f64, 3> + ?Sized,
+{
+    let (hx, hy) = (EPSILON * GRID_CELL_H / GRID_CELL_W, EPSILON);
+    let d_dx = (noise.get([x + hx, y, z]) - noise.get([x - hx, y, z])) / (2.0 * EPSILON);
+    let d_dy = (noise.get([x, y + hy, z]) - noise.get([x, y - hy, z])) / (2.0 * EPSILON);
+
+    [d_dx, d_dy]
+}
+
+/// The curl of `noise` at `p`, treating it as the z component of a 3D vector potential.
+pub fn curl<N>(noise: &N, p: [f64; 3]) -> [f64; 2]
+where
+    N: NoiseFn<f64, 3> + ?Sized,
+{
+    let [d_dx, d_dy] = gradient(noise, p);
+
+    [d_dy, -d_dx]
+}
+
+/// Multiplies the noise by TAU and points that way with unit length.
+pub struct AngleField {
+    noise: SharedNoiseFn,
+}
+
+impl AngleField {
+    pub fn new(noise: SharedNoiseFn) -> Self {
+        Self { noise }
+    }
+}
+
+impl VectorField for AngleField {
+    fn sample(&self, x: f64, y: f64, t: f64) -> [f64; 2] {
+        let angle = self.noise.get([x, y, t]) * TAU;
+
+        [angle.cos(), angle.sin()]
+    }
+}
+
+/// Points up the slope of the noise, optionally turned 90° to follow lines of equal noise.
+pub struct GradientField {
+    noise: SharedNoiseFn,
+    rotated: bool,
+}
+
+impl GradientField {
+    pub fn new(noise: SharedNoiseFn, rotated: bool) -> Self {
+        Self { noise, rotated }
+    }
+}
+
+impl VectorField for GradientField {
+    fn sample(&self, x: f64, y: f64, t: f64) -> [f64; 2] {
+        let [dx, dy] = gradient(self.noise.as_ref(), [x, y, t]);
+
+        if self.rotated { [-dy, dx] } else { [dx, dy] }
+    }
+}
+
+/// Treats the noise as a potential and follows its curl, which has no sinks or sources.
+pub struct CurlField {
+    noise: SharedNoiseFn,
+}
+
+impl CurlField {
+    pub fn new(noise: SharedNoiseFn) -> Self {
+        Self { noise }
+    }
+}
+
+impl VectorField for CurlField {
+    fn sample(&self, x: f64, y: f64, t: f64) -> [f64; 2] {
+        curl(self.noise.as_ref(), [x, y, t])
+    }
+}
+
+/// A field given directly by a function of position and time.
+pub struct AnalyticField<F> {
+    f: F,
+}
+
+impl<F> AnalyticField<F>
+where
+    F: Fn(f64, f64, f64) -> [f64; 2],
+{
+    pub fn new(f: F) -> Self {
+        Self { f }
+    }
+}
+
+impl<F> VectorField for AnalyticField<F>
+where
+    F: Fn(f64, f64, f64) -> [f64; 2],
+{
+    fn sample(&self, x: f64, y: f64, t: f64) -> [f64; 2] {
+        (self.f)(x, y, t)
+    }
+}
+
+/// The weighted sum of other fields.
+#[derive(Default)]
+pub struct CompositeField {
+    fields: Vec<(f64, Box<dyn VectorField>)>,
+}
+
+impl CompositeField {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with(mut self, weight: f64, field: Box<dyn VectorField>) -> Self {
+        self.fields.push((weight, field));
+        self
+    }
+}
+
+impl VectorField for CompositeField {
+    fn sample(&self, x: f64, y: f64, t: f64) -> [f64; 2] {
+        self.fields
+            .iter()
+            .fold([0.0, 0.0], |[sum_x, sum_y], (weight, field)| {
+                let [dx, dy] = field.sample(x, y, t);
+                [sum_x + weight * dx, sum_y + weight * dy]
+            })
+    }
+}
+
+/// How the scalar noise is turned into directions.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, ValueEnum)]
+#[serde(rename_all = "snake_case")]
+pub enum FieldMode {
+    /// Multiply the noise by TAU and use that as an angle
+    #[default]
+    Angle,
+    /// Point up the slope of the noise
+    Gradient,
+    /// Follow lines of equal noise, at right angles to the gradient
+    IsoLines,
+    /// Follow the curl of the noise, which swirls without sinks or sources
+    Curl,
+    /// Follow a `Formula` written by hand
+    Formula,
+}
+
+impl FieldMode {
+    /// Build the field this mode derives from `noise`. `formula` is only used by
+    /// `FieldMode::Formula`, which falls back to `FieldMode::Angle` without one, and builds the
+    /// generators it calls with `seed` and `loop_length` like `noise` was built.
+    pub fn field(
+        self,
+        noise: SharedNoiseFn,
+        formula: Option<&Formula>,
+        seed: u32,
+        loop_length: Option<f64>,
+    ) -> Box<dyn VectorField> {
+        match (self, formula) {
+            (FieldMode::Angle, _) | (FieldMode::Formula, None) => Box::new(AngleField::new(noise)),
+            (FieldMode::Gradient, _) => Box::new(GradientField::new(noise, false)),
+            (FieldMode::IsoLines, _) => Box::new(GradientField::new(noise, true)),
+            (FieldMode::Curl, _) => Box::new(CurlField::new(noise)),
+            (FieldMode::Formula, Some(formula)) => {
+                Box::new(FormulaField::new(formula.clone(), noise, seed, loop_length))
+            }
+        }
+    }
+}
+
+impl Choice for FieldMode {
+    const ALL: &'static [Self] = &[
+        FieldMode::Angle,
+        FieldMode::Gradient,
+        FieldMode::IsoLines,
+        FieldMode::Curl,
+        FieldMode::Formula,
+    ];
+}
+
+impl fmt::Display for FieldMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FieldMode::Angle => write!(f, "angle"),
+            FieldMode::Gradient => write!(f, "gradient"),
+            FieldMode::IsoLines => write!(f, "iso-lines"),
+            FieldMode::Curl => write!(f, "curl"),
+            FieldMode::Formula => write!(f, "formula"),
+        }
+    }
+}
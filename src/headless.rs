@@ -0,0 +1,265 @@
+use crate::{
+    export::{save_export, unused_svg_filepath},
+    field::{FieldMode, Formula},
+    noise::{NoiseGraph, NOISE_FN_COUNT},
+    layers::{LayerOutput, LayerRule, LayerSettings},
+    mask::Mask,
+    page::{Orientation, Paper},
+    preset::Preset,
+    visualizer::{new_visualizer_by_index, VISUALIZER_COUNT},
+};
+use clap::Args;
+use log::info;
+use std::{
+    error::Error,
+    path::{Path, PathBuf},
+};
+
+/// Render a single SVG without opening a window.
+///
+/// Anything that isn't passed comes from `--preset`, or the defaults if there's no preset.
+#[derive(Args, Debug)]
+pub struct RenderArgs {
+    /// Start from a scene preset saved with the P key
+    #[arg(long)]
+    pub preset: Option<PathBuf>,
+    /// Index of the noise generator to use (same order as the N and B keys)
+    #[arg(long, value_parser = clap::value_parser!(u8).range(0..NOISE_FN_COUNT as i64))]
+    pub noise: Option<u8>,
+    /// Combine noise generators, e.g. "Blend(Perlin, Worley, control = Fbm)". Replaces --noise
+    #[arg(long)]
+    pub noise_graph: Option<NoiseGraph>,
+    /// Index of the visualizer to use (same order as the J and K keys)
+    #[arg(long, value_parser = clap::value_parser!(u8).range(0..VISUALIZER_COUNT as i64))]
+    pub visualizer: Option<u8>,
+    /// Seed for the noise generator and any random noise parameters
+    #[arg(long)]
+    pub seed: Option<u32>,
+    /// Starting offsets into the noise, like moving around with the arrow keys
+    #[arg(long, allow_negative_numbers = true)]
+    pub x_offset: Option<f64>,
+    #[arg(long, allow_negative_numbers = true)]
+    pub y_offset: Option<f64>,
+    #[arg(long, allow_negative_numbers = true)]
+    pub z_offset: Option<f64>,
+    /// The "scale" of the noise, smaller values zoom in
+    #[arg(long)]
+    pub scale: Option<f64>,
+    /// How much `z_offset` changes with each frame
+    #[arg(long)]
+    pub speed: Option<f64>,
+    /// Loop time around every this many frames, so that an animation repeats seamlessly
+    #[arg(long, value_parser = clap::value_parser!(u32).range(1..))]
+    pub loop_period: Option<u32>,
+    /// Wrap the field around the edges so that the SVG tiles seamlessly
+    #[arg(long)]
+    pub tiled: bool,
+    /// How the noise is turned into directions
+    #[arg(long, value_enum)]
+    pub field_mode: Option<FieldMode>,
+    /// Follow a formula such as "angle = sin(x * 60)" or "(dx, dy) = (-y, x)" instead of the noise
+    #[arg(long)]
+    pub formula: Option<Formula>,
+    /// Paper to lay the SVG out on: a4, a3, letter etc. or a size in mm like 300x200
+    #[arg(long)]
+    pub paper: Option<Paper>,
+    #[arg(long, value_enum)]
+    pub orientation: Option<Orientation>,
+    /// Blank space to keep around the edges of the paper, in mm
+    #[arg(long)]
+    pub margin: Option<f64>,
+    /// Also write a .gcode file next to the SVG
+    #[arg(long)]
+    pub gcode: bool,
+    /// Also write an .hpgl file next to the SVG
+    #[arg(long)]
+    pub hpgl: bool,
+    /// The G-code that lifts the pen, e.g. "M5"
+    #[arg(long)]
+    pub pen_up: Option<String>,
+    /// The G-code that puts the pen down, e.g. "M3 S90"
+    #[arg(long)]
+    pub pen_down: Option<String>,
+    /// The G-code that waits for the pen to be changed between pens, e.g. "M0"
+    #[arg(long)]
+    pub pen_change: Option<String>,
+    /// How fast the pen draws, in mm per minute
+    #[arg(long)]
+    pub draw_feed_rate: Option<f64>,
+    /// How fast the pen moves while it's up, in mm per minute
+    #[arg(long)]
+    pub travel_feed_rate: Option<f64>,
+    /// Split the export between this many pens, each with its own colour
+    #[arg(long, value_parser = clap::value_parser!(u8).range(1..=LayerSettings::MAX_PENS as i64))]
+    pub pens: Option<u8>,
+    /// What decides which pen draws each part of the export
+    #[arg(long, value_enum)]
+    pub layer_rule: Option<LayerRule>,
+    /// Put each pen in an Inkscape layer of one SVG, or in a file of its own
+    #[arg(long, value_enum)]
+    pub layer_output: Option<LayerOutput>,
+    /// Clip the SVG to "circle X Y RADIUS", "polygon X,Y X,Y X,Y...", "text FONT.ttf SOME WORDS"
+    /// or "image MASK.png", in canvas pixels
+    #[arg(long)]
+    pub mask: Option<Mask>,
+    /// Keep what's outside the mask instead of what's inside it
+    #[arg(long)]
+    pub invert_mask: bool,
+    /// How many times to update the visualizer before exporting
+    #[arg(long, default_value_t = 1)]
+    pub frames: usize,
+    /// Write an SVG after every frame, numbered from 1, instead of only after the last one
+    #[arg(long)]
+    pub every_frame: bool,
+    /// Where to write the SVG. Defaults to a new file in SVG_EXPORT_DIRECTORY
+    #[arg(short, long)]
+    pub output: Option<PathBuf>,
+}
+
+impl RenderArgs {
+    fn preset(&self) -> Result<Preset, Box<dyn Error>> {
+        let mut preset = match &self.preset {
+            Some(path) => Preset::load(path)?,
+            None => Preset::default(),
+        };
+
+        if let Some(noise) = self.noise {
+            preset.noise = noise as usize;
+            preset.noise_graph = None;
+        }
+        if let Some(noise_graph) = &self.noise_graph {
+            preset.noise_graph = Some(noise_graph.clone());
+        }
+        if let Some(visualizer) = self.visualizer {
+            preset.visualizer = visualizer as usize;
+        }
+        if let Some(seed) = self.seed {
+            preset.seed = seed;
+        }
+        if let Some(x_offset) = self.x_offset {
+            preset.x_offset = x_offset;
+        }
+        if let Some(y_offset) = self.y_offset {
+            preset.y_offset = y_offset;
+        }
+        if let Some(z_offset) = self.z_offset {
+            preset.z_offset = z_offset;
+        }
+        if let Some(scale) = self.scale {
+            preset.scale = scale;
+        }
+        if let Some(speed) = self.speed {
+            preset.speed = speed;
+        }
+        if let Some(loop_period) = self.loop_period {
+            preset.loop_period = Some(loop_period);
+        }
+        if self.tiled {
+            preset.tiled = true;
+        }
+        if let Some(paper) = self.paper {
+            preset.page.paper = paper;
+        }
+        if let Some(orientation) = self.orientation {
+            preset.page.orientation = orientation;
+        }
+        if let Some(margin) = self.margin {
+            preset.page.margin = margin;
+        }
+        preset.page.validate()?;
+        if self.gcode {
+            preset.plotter.gcode = true;
+        }
+        if self.hpgl {
+            preset.plotter.hpgl = true;
+        }
+        if let Some(pen_up) = &self.pen_up {
+            preset.plotter.pen_up = pen_up.clone();
+        }
+        if let Some(pen_down) = &self.pen_down {
+            preset.plotter.pen_down = pen_down.clone();
+        }
+        if let Some(pen_change) = &self.pen_change {
+            preset.plotter.pen_change = pen_change.clone();
+        }
+        if let Some(draw_feed_rate) = self.draw_feed_rate {
+            preset.plotter.draw_feed_rate = draw_feed_rate;
+        }
+        if let Some(travel_feed_rate) = self.travel_feed_rate {
+            preset.plotter.travel_feed_rate = travel_feed_rate;
+        }
+        if let Some(pens) = self.pens {
+            preset.layers.pens = pens as usize;
+        }
+        if let Some(layer_rule) = self.layer_rule {
+            preset.layers.rule = layer_rule;
+        }
+        if let Some(layer_output) = self.layer_output {
+            preset.layers.output = layer_output;
+        }
+        if let Some(mask) = &self.mask {
+            preset.mask = Some(mask.clone());
+        }
+        if self.invert_mask {
+            let mask = preset.mask.as_mut().ok_or("--invert-mask needs a mask to invert")?;
+            mask.set_inverted(true);
+        }
+        if let Some(field_mode) = self.field_mode {
+            preset.field_mode = field_mode;
+        }
+        if let Some(formula) = &self.formula {
+            preset.use_formula(formula.clone());
+        }
+
+        Ok(preset)
+    }
+}
+
+pub fn render(args: RenderArgs) -> Result<(), Box<dyn Error>> {
+    let preset = args.preset()?;
+    let svg_filepath = match args.output {
+        Some(output) => output,
+        None => {
+            let base_path = std::env::var("SVG_EXPORT_DIRECTORY").map_err(|_| {
+                "SVG_EXPORT_DIRECTORY must be set to a valid directory when --output isn't passed"
+            })?;
+            unused_svg_filepath(base_path, preset.seed)
+        }
+    };
+
+    let mut params = preset.to_params();
+    let mut visualizer = new_visualizer_by_index(preset.visualizer, &params);
+
+    for frame in 1..=args.frames {
+        visualizer.update(&mut params);
+
+        if args.every_frame {
+            save_export(visualizer.as_ref(), &params, &numbered(&svg_filepath, frame))?;
+        }
+    }
+
+    if args.every_frame {
+        info!(
+            "{} SVGs successfully exported next to {}",
+            args.frames,
+            &svg_filepath.to_string_lossy()
+        );
+        return Ok(());
+    }
+
+    save_export(visualizer.as_ref(), &params, &svg_filepath)?;
+    info!(
+        "SVG successfully exported to {}",
+        &svg_filepath.to_string_lossy()
+    );
+
+    Ok(())
+}
+
+/// `frames.svg` becomes `frames-0001.svg` for frame 1, so that frames sort in order.
+fn numbered(path: &Path, frame: usize) -> PathBuf {
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    let extension = path.extension().unwrap_or_default().to_string_lossy();
+
+    path.with_file_name(format!("{stem}-{frame:04}.{extension}"))
+}
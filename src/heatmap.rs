@@ -0,0 +1,210 @@
+use crate::{
+    colormap::Colormap,
+    consts::{GRID_CELL_H, GRID_CELL_W, GRID_SIZE_X, GRID_SIZE_Y, SCREEN_H, SCREEN_W},
+    settings::Setting,
+    visualizer::{Visualizer, VisualizerParams},
+};
+use log::info;
+use macroquad::prelude::*;
+use svg::node::element;
+
+const LEGEND_W: f64 = 24.0;
+const LEGEND_H: f64 = 320.0;
+const LEGEND_X: f64 = SCREEN_W as f64 - LEGEND_W - 80.0;
+const LEGEND_Y: f64 = (SCREEN_H as f64 - LEGEND_H) / 2.0;
+/// How many bands the legend's gradient is drawn with
+const LEGEND_STEPS: usize = 64;
+const LEGEND_FONT_SIZE: f64 = 18.0;
+
+/// Shades every grid cell by the raw value of the noise.
+pub struct Heatmap {
+    values: Vec<f64>,
+    /// The values that map to each end of the colormap
+    range: (f64, f64),
+    colormap: Colormap,
+    legend: bool,
+}
+
+impl Heatmap {
+    pub fn new(params: &VisualizerParams) -> Self {
+        let mut heatmap = Self {
+            values: vec![0.0; GRID_SIZE_X * GRID_SIZE_Y],
+            range: (-1.0, 1.0),
+            colormap: params.settings.colormap,
+            legend: params.settings.legend,
+        };
+        heatmap.sample(params);
+
+        heatmap
+    }
+
+    fn sample(&mut self, params: &VisualizerParams) {
+        for (i, value) in self.values.iter_mut().enumerate() {
+            let (x, y) = ((i % GRID_SIZE_X) as f64, (i / GRID_SIZE_X) as f64);
+            *value = params.noise_at([x * GRID_CELL_W, y * GRID_CELL_H]);
+        }
+
+        let min = self.values.iter().copied().fold(f64::INFINITY, f64::min);
+        let max = self.values.iter().copied().fold(f64::NEG_INFINITY, f64::max);
+
+        self.colormap = params.settings.colormap;
+        self.legend = params.settings.legend;
+        self.range = if self.colormap.is_diverging() {
+            let extent = min.abs().max(max.abs());
+            (-extent, extent)
+        } else {
+            (min, max)
+        };
+    }
+
+    /// Where `value` falls in the colormap, from 0 to 1.
+    fn position_of(&self, value: f64) -> f64 {
+        let (min, max) = self.range;
+
+        if max > min {
+            (value - min) / (max - min)
+        } else {
+            0.5
+        }
+    }
+
+    fn cells(&self) -> impl Iterator<Item = ([f64; 2], f64)> + '_ {
+        self.values.iter().enumerate().map(|(i, value)| {
+            let (x, y) = ((i % GRID_SIZE_X) as f64, (i / GRID_SIZE_X) as f64);
+            ([x * GRID_CELL_W, y * GRID_CELL_H], self.position_of(*value))
+        })
+    }
+
+    /// The legend's bands from top (the highest value) to bottom, as a position in the colormap
+    /// and the band's top edge.
+    fn legend_bands() -> impl Iterator<Item = (f64, f64)> {
+        let band_h = LEGEND_H / LEGEND_STEPS as f64;
+
+        (0..LEGEND_STEPS).map(move |i| {
+            let t = 1.0 - (i as f64 + 0.5) / LEGEND_STEPS as f64;
+            (t, LEGEND_Y + i as f64 * band_h)
+        })
+    }
+
+    fn legend_labels(&self) -> [(String, f64); 2] {
+        let (min, max) = self.range;
+
+        [
+            (format!("{max:.3}"), LEGEND_Y - LEGEND_FONT_SIZE / 2.0),
+            (format!("{min:.3}"), LEGEND_Y + LEGEND_H + LEGEND_FONT_SIZE * 1.5),
+        ]
+    }
+}
+
+impl Visualizer for Heatmap {
+    fn update(&mut self, params: &mut VisualizerParams) {
+        self.sample(params);
+        // The other grid visualizers move through time once per row, so keep pace with them
+        params.z_offset += params.noise_speed * GRID_SIZE_Y as f64;
+    }
+
+    fn build_svg_document_from_state(&self) -> svg::Document {
+        let doc = svg::Document::new().set("viewBox", (0, 0, SCREEN_W, SCREEN_H));
+
+        let mut group = element::Group::new()
+            .set("stroke", "none")
+            .set("shape-rendering", "crispEdges");
+
+        info!("rendering {} heatmap cells", self.values.len());
+
+        for ([x, y], t) in self.cells() {
+            let cell = element::Rectangle::new()
+                .set("x", x)
+                .set("y", y)
+                .set("width", GRID_CELL_W)
+                .set("height", GRID_CELL_H)
+                .set("fill", self.colormap.hex(t));
+
+            group = group.add(cell);
+        }
+
+        let mut doc = doc.add(group);
+
+        if self.legend {
+            let band_h = LEGEND_H / LEGEND_STEPS as f64;
+            let mut legend = element::Group::new()
+                .set("stroke", "none")
+                .set("shape-rendering", "crispEdges");
+
+            for (t, y) in Self::legend_bands() {
+                let band = element::Rectangle::new()
+                    .set("x", LEGEND_X)
+                    .set("y", y)
+                    .set("width", LEGEND_W)
+                    .set("height", band_h)
+                    .set("fill", self.colormap.hex(t));
+
+                legend = legend.add(band);
+            }
+
+            for (label, y) in self.legend_labels() {
+                let text = element::Text::new(label)
+                    .set("x", LEGEND_X)
+                    .set("y", y)
+                    .set("font-size", LEGEND_FONT_SIZE)
+                    .set("font-family", "sans-serif")
+                    .set("fill", "black");
+
+                legend = legend.add(text);
+            }
+
+            doc = doc.add(legend);
+        }
+
+        let bounding_rect = element::Rectangle::new()
+            .set("width", SCREEN_W)
+            .set("height", SCREEN_H)
+            .set("fill", "none")
+            .set("stroke", "black")
+            .set("stroke-width", "1mm");
+
+        doc.add(bounding_rect)
+    }
+
+    fn render(&self) {
+        let rgb_to_color = |[r, g, b]: [f64; 3]| Color::new(r as f32, g as f32, b as f32, 1.0);
+
+        for ([x, y], t) in self.cells() {
+            let color = rgb_to_color(self.colormap.rgb(t));
+            draw_rectangle(
+                x as f32,
+                y as f32,
+                GRID_CELL_W as f32,
+                GRID_CELL_H as f32,
+                color,
+            );
+        }
+
+        if self.legend {
+            let band_h = LEGEND_H / LEGEND_STEPS as f64;
+
+            for (t, y) in Self::legend_bands() {
+                let color = rgb_to_color(self.colormap.rgb(t));
+                draw_rectangle(
+                    LEGEND_X as f32,
+                    y as f32,
+                    LEGEND_W as f32,
+                    band_h as f32,
+                    color,
+                );
+            }
+
+            for (label, y) in self.legend_labels() {
+                draw_text(&label, LEGEND_X as f32, y as f32, LEGEND_FONT_SIZE as f32, WHITE);
+            }
+        }
+    }
+
+    fn shows_noise_alone(&self) -> bool {
+        true
+    }
+
+    fn settings(&self) -> &'static [Setting] {
+        &[Setting::Colormap, Setting::Legend]
+    }
+}
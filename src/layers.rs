@@ -0,0 +1,260 @@
+use crate::{
+    consts::{GRID_CELL_H, GRID_CELL_W, GRID_SIZE_X, GRID_SIZE_Y},
+    plot::split_layers,
+    settings::{Choice, MagnitudeSource},
+    visualizer::VisualizerParams,
+};
+use ::rand::{prelude::StdRng, Rng, SeedableRng};
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+use std::{f64::consts::TAU, fmt};
+use svg::node::{element::Group, Node};
+
+/// Pen colours used when `LayerSettings::colors` is empty.
+const DEFAULT_COLORS: [&str; 8] = [
+    "#000000", "#d62728", "#1f77b4", "#2ca02c", "#ff7f0e", "#9467bd", "#8c564b", "#e377c2",
+];
+
+/// What decides which pen draws each part of an export.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, ValueEnum)]
+#[serde(rename_all = "snake_case")]
+pub enum LayerRule {
+    /// Equal bands of the noise value, from -1 to 1
+    #[default]
+    NoiseBand,
+    /// Which way the field points, in equal slices of a full turn
+    Angle,
+    /// How strong the field is, measured like the arrows' magnitude setting
+    Magnitude,
+    /// A random pen for each path, picked with the seed
+    Random,
+}
+
+impl Choice for LayerRule {
+    const ALL: &'static [Self] = &[
+        LayerRule::NoiseBand,
+        LayerRule::Angle,
+        LayerRule::Magnitude,
+        LayerRule::Random,
+    ];
+}
+
+impl fmt::Display for LayerRule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LayerRule::NoiseBand => write!(f, "noise band"),
+            LayerRule::Angle => write!(f, "angle"),
+            LayerRule::Magnitude => write!(f, "magnitude"),
+            LayerRule::Random => write!(f, "random"),
+        }
+    }
+}
+
+/// Where each pen's layer is exported.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, ValueEnum)]
+#[serde(rename_all = "snake_case")]
+pub enum LayerOutput {
+    /// One file with an Inkscape layer for each pen
+    #[default]
+    InkscapeLayers,
+    /// A separate file for each pen
+    Files,
+}
+
+impl Choice for LayerOutput {
+    const ALL: &'static [Self] = &[LayerOutput::InkscapeLayers, LayerOutput::Files];
+}
+
+impl fmt::Display for LayerOutput {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LayerOutput::InkscapeLayers => write!(f, "Inkscape layers"),
+            LayerOutput::Files => write!(f, "file per pen"),
+        }
+    }
+}
+
+/// How exports are split between pens for multi-colour plots.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LayerSettings {
+    /// How many pens to split exports between. With one pen, exports aren't split at all.
+    pub pens: usize,
+    pub rule: LayerRule,
+    /// The stroke colour of each pen's layer, as any SVG colour. Pens past the end of the list start
+    /// over from the beginning, and an empty list uses a built in palette.
+    pub colors: Vec<String>,
+    pub output: LayerOutput,
+}
+
+impl LayerSettings {
+    pub const FIELD_NAMES: [&str; 3] = ["pens", "split pens by", "pen output"];
+    pub const MAX_PENS: usize = 8;
+
+    /// The stroke colour of the layer drawn by `pen`, counting from 0.
+    pub fn color(&self, pen: usize) -> &str {
+        match self.colors.len() {
+            0 => DEFAULT_COLORS[pen % DEFAULT_COLORS.len()],
+            len => &self.colors[pen % len],
+        }
+    }
+
+    /// Split an exported SVG into one document for each pen, coloured with that pen's colour.
+    /// Exports for a single pen are left as they are.
+    pub fn split(&self, document: svg::Document, params: &VisualizerParams) -> Vec<svg::Document> {
+        if self.pens <= 1 {
+            return vec![document];
+        }
+
+        let pens = self.pens;
+        let band = |t: f64| ((t * pens as f64).floor().max(0.0) as usize).min(pens - 1);
+        let max_magnitude = match self.rule {
+            LayerRule::Magnitude => grid_points()
+                .map(|p| magnitude_at(params, p))
+                .fold(0.0, f64::max),
+            _ => 0.0,
+        };
+        let mut rng = StdRng::seed_from_u64(params.seed as u64);
+        let mut random_pen = None;
+
+        let layer_of = |stroke: usize, p: [f64; 2]| match self.rule {
+            LayerRule::NoiseBand => band((params.noise_at(p) + 1.0) / 2.0),
+            LayerRule::Angle => {
+                let [dx, dy] = params.vector_at(p);
+                band(dy.atan2(dx).rem_euclid(TAU) / TAU)
+            }
+            LayerRule::Magnitude if max_magnitude > 0.0 => {
+                band(magnitude_at(params, p) / max_magnitude)
+            }
+            LayerRule::Magnitude => 0,
+            LayerRule::Random => match random_pen {
+                Some((for_stroke, pen)) if for_stroke == stroke => pen,
+                _ => {
+                    let pen = rng.random_range(0..pens);
+                    random_pen = Some((stroke, pen));
+                    pen
+                }
+            },
+        };
+
+        split_layers(document, pens, layer_of)
+            .into_iter()
+            .enumerate()
+            .map(|(pen, mut document)| {
+                recolor(&mut document, self.color(pen));
+                document
+            })
+            .collect()
+    }
+
+    /// Format the field at `field` (an index into `FIELD_NAMES`) for display.
+    pub fn field_value(&self, field: usize) -> String {
+        match field {
+            0 => self.pens.to_string(),
+            1 => self.rule.to_string(),
+            2 => self.output.to_string(),
+            _ => unreachable!(),
+        }
+    }
+
+    /// Nudge the field at `field` (an index into `FIELD_NAMES`) up or down by one step.
+    pub fn nudge(&mut self, field: usize, increase: bool) {
+        match field {
+            0 if increase => self.pens = (self.pens + 1).min(Self::MAX_PENS),
+            0 => self.pens = self.pens.saturating_sub(1).max(1),
+            1 => self.rule = self.rule.cycled(increase),
+            2 => self.output = self.output.cycled(increase),
+            _ => unreachable!(),
+        }
+    }
+}
+
+impl Default for LayerSettings {
+    fn default() -> Self {
+        Self {
+            pens: 1,
+            rule: LayerRule::default(),
+            colors: Vec::new(),
+            output: LayerOutput::default(),
+        }
+    }
+}
+
+/// Put each pen's document in an Inkscape layer of one SVG, named after the pen, so that they can
+/// be plotted one at a time. The documents should all be laid out on the same page.
+pub fn inkscape_layers(documents: Vec<svg::Document>) -> svg::Document {
+    let mut combined = svg::Document::new();
+
+    for (i, mut document) in documents.into_iter().enumerate() {
+        if i == 0 {
+            for (name, value) in document.get_attributes().into_iter().flatten() {
+                combined = combined.set(name.clone(), value.clone());
+            }
+        }
+
+        let pen = i + 1;
+        let mut layer = Group::new()
+            .set("inkscape:groupmode", "layer")
+            .set("inkscape:label", format!("Pen {pen}"))
+            .set("id", format!("pen-{pen}"));
+        for child in document
+            .get_children_mut()
+            .map(std::mem::take)
+            .unwrap_or_default()
+        {
+            layer = layer.add(child);
+        }
+        combined = combined.add(layer);
+    }
+
+    combined.set(
+        "xmlns:inkscape",
+        "http://www.inkscape.org/namespaces/inkscape",
+    )
+}
+
+/// Every point of the grid the grid based visualizers sample.
+fn grid_points() -> impl Iterator<Item = [f64; 2]> {
+    (0..GRID_SIZE_Y).flat_map(|y| {
+        (0..GRID_SIZE_X).map(move |x| [x as f64 * GRID_CELL_W, y as f64 * GRID_CELL_H])
+    })
+}
+
+/// How strong the field is at `p`, by the same measure the arrows use.
+fn magnitude_at(params: &VisualizerParams, p: [f64; 2]) -> f64 {
+    match params.settings.magnitude_source {
+        MagnitudeSource::Constant => 1.0,
+        MagnitudeSource::NoiseAmplitude => params.noise_at(p).abs(),
+        MagnitudeSource::GradientNorm => {
+            let [dx, dy] = params.gradient_at(p);
+            dx.hypot(dy)
+        }
+        MagnitudeSource::FieldStrength => {
+            let [dx, dy] = params.vector_at(p);
+            dx.hypot(dy)
+        }
+    }
+}
+
+/// Draw everything under `node` in `color`: every stroke that isn't turned off, and every fill
+/// that's black like the filled arrow heads.
+fn recolor(node: &mut dyn Node, color: &str) {
+    if let Some(attributes) = node.get_attributes_mut() {
+        for (name, value) in attributes.iter_mut() {
+            let recolored = match name.as_str() {
+                "stroke" => &**value != "none",
+                "fill" => &**value == "black",
+                _ => false,
+            };
+            if recolored {
+                *value = color.into();
+            }
+        }
+    }
+
+    if let Some(children) = node.get_children_mut() {
+        for child in children {
+            recolor(child.as_mut(), color);
+        }
+    }
+}
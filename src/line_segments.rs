@@ -3,7 +3,8 @@ use crate::{
         GRID_CELL_H, GRID_CELL_W, GRID_SIZE_X, GRID_SIZE_Y, SCREEN_H, SCREEN_W, VECTOR_SCALE,
         VECTOR_WIDTH,
     },
-    visualizer::{Visualizer, VisualizerParams},
+    field::FieldMode,
+    visualizer::{direction, Visualizer, VisualizerParams},
 };
 use log::info;
 use std::f64::consts::TAU;
@@ -35,6 +36,13 @@ impl LineSegment {
         self.points[1][0] = x * self.scale + self.points[0][0];
         self.points[1][1] = y * self.scale + self.points[0][1];
     }
+
+    /// Stretch the line by `factor`, keeping `p0` where it is.
+    pub fn rescale(&mut self, factor: f64) {
+        let [[x0, y0], [x1, y1]] = self.points;
+        self.points[1] = [x0 + (x1 - x0) * factor, y0 + (y1 - y0) * factor];
+        self.scale *= factor;
+    }
 }
 
 impl Default for LineSegment {
@@ -60,7 +68,10 @@ impl LineSegments {
                     y * GRID_CELL_H + GRID_CELL_H / 2.0,
                 ];
 
-                let angle = params.noise_fn.get([x, y, params.z_offset]) * TAU;
+                let [dx, dy] = params
+                    .direction_at([x * GRID_CELL_W, y * GRID_CELL_H])
+                    .unwrap_or([1.0, 0.0]);
+                let angle = dy.atan2(dx);
 
                 line_segments.push(LineSegment::from_angle(p0, angle, VECTOR_SCALE));
             }
@@ -72,22 +83,50 @@ impl LineSegments {
 
 impl Visualizer for LineSegments {
     fn update(&mut self, params: &mut VisualizerParams) {
-        let mut y_offset = 0.0 + params.base_y_offset;
+        // Only the angle field is as strong everywhere, which the formula mode falls back to
+        // without a formula. Every other field, and the angle field with flows added to it, gives
+        // the lines their length too
+        let angle_field = match params.field_mode {
+            FieldMode::Angle => true,
+            FieldMode::Formula => params.formula.is_none(),
+            _ => false,
+        };
+        let sized_by_field = !angle_field || !params.features.is_empty();
+
         for y in 0..GRID_SIZE_Y {
-            let mut x_offset = 0.0 + params.base_x_offset;
             for x in 0..GRID_SIZE_X {
-                let angle = params.noise_fn.get([x_offset, y_offset, params.z_offset]) * TAU;
+                let p = [x as f64 * GRID_CELL_W, y as f64 * GRID_CELL_H];
+                let vector = params.vector_at(p);
+                let [dx, dy] = direction(vector).unwrap_or([0.0, 0.0]);
                 let next_line_to_draw = &mut self.line_segments[x + y * GRID_SIZE_X];
 
-                next_line_to_draw.scale = VECTOR_SCALE * angle.atan();
-                next_line_to_draw.set_p1_relative(angle.cos(), angle.sin());
-
-                x_offset += params.noise_scale;
+                next_line_to_draw.scale = if sized_by_field {
+                    // Scaled to the strongest vector once the whole grid is sampled
+                    if dx == 0.0 && dy == 0.0 { 0.0 } else { vector[0].hypot(vector[1]) }
+                } else {
+                    // atan(noise * TAU) shrinks lines where the noise is close to zero, and goes
+                    // negative along with the noise, which flips lines to point against the field
+                    VECTOR_SCALE * (params.noise_at(p) * TAU).atan()
+                };
+                next_line_to_draw.set_p1_relative(dx, dy);
             }
-            y_offset += params.noise_scale;
             // TODO is this really meant to happen per y?
             params.z_offset += params.noise_speed;
         }
+
+        if sized_by_field {
+            let strongest = self
+                .line_segments
+                .iter()
+                .map(|line| line.scale)
+                .fold(0.0, f64::max);
+
+            if strongest > 0.0 {
+                for line in self.line_segments.iter_mut() {
+                    line.rescale(VECTOR_SCALE / strongest);
+                }
+            }
+        }
     }
 
     fn build_svg_document_from_state(&self) -> svg::Document {
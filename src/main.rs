@@ -3,27 +3,95 @@
 //! Controls:
 //!
 //! - N | B       Cycle forward and back through Noise types
-//! - J | K       Change the visualizer kind (circles vs. lines)
+//! - J | K       Change the visualizer kind (lines, circles, streamlines, particles, arrows...)
 //! - + | -       Zoom in and out by changing the "scale" of the noise
 //! - ] | [       Speed up or slow down the rate of change
+//! - T           Loop time around so the animation repeats, or let it run on
+//! - E           Wrap the field around the edges so that it tiles seamlessly
+//! - . | ,       Step the seed forward and back
+//! - S           Pick a random seed
+//! - W           Re-roll the domain warp offsets, keeping the seed
+//! - G           Turn noise into directions by angle, gradient, iso-lines or curl
+//! - F           Place the selected analytic flow at the mouse
+//! - Backspace   Remove the most recently placed flow
+//! - Tab         Select the next setting of the active noise, visualizer or paper
+//! - PgUp | PgDn Increase or decrease the selected setting
 //! - Arrow Keys  Move around by offsetting generated noise
 //! - O           Reset your offset back to the origin
 //! - R           Reset speed, scale, and offset
+//! - X           Export the current vectors to an SVG laid out on the paper
+//! - P           Save the current scene as a preset
+//! - L           Load the most recently saved preset
 //! - H           Show or hide this help screen
 //! - Esc         Quit
+//!
+//! Run with `--preset <FILE>` to start from a saved scene, `--noise-graph <GRAPH>` to combine
+//! noises, `--formula <FORMULA>` to follow a field written by hand, `--mask <MASK>` to keep to a
+//! shape, some text or an image, or `render --help` to export an SVG without opening a window.
 
+mod arrows;
 mod circles;
+mod colormap;
 mod consts;
+mod contours;
 mod counter;
+mod evenly_spaced_streamlines;
+mod export;
+mod field;
+mod headless;
+mod heatmap;
+mod layers;
 mod line_segments;
+mod mask;
 mod noise;
+mod page;
+mod plot;
+mod plotter;
+mod particles;
+mod preset;
+mod settings;
+mod spatial_grid;
 mod state;
+mod streamlines;
 mod visualizer;
 
+use clap::{Parser, Subcommand};
 use consts::{SCREEN_H, SCREEN_W};
-use log::warn;
+use field::Formula;
+use noise::NoiseGraph;
+use headless::RenderArgs;
+use log::{error, warn};
+use mask::Mask;
+use preset::Preset;
 use state::State;
 use macroquad::prelude::*;
+use std::path::PathBuf;
+
+#[derive(Parser)]
+#[command(version, about = "Vector Field Visualization")]
+struct Cli {
+    /// Start from a scene preset saved with the P key
+    #[arg(long)]
+    preset: Option<PathBuf>,
+    /// Combine noise generators, e.g. "Blend(Perlin, Worley, control = Fbm)"
+    #[arg(long)]
+    noise_graph: Option<NoiseGraph>,
+    /// Follow a formula such as "angle = sin(x * 60)" or "(dx, dy) = (-y, x)" instead of the noise
+    #[arg(long)]
+    formula: Option<Formula>,
+    /// Limit the visualization to "circle X Y RADIUS", "polygon X,Y X,Y X,Y...",
+    /// "text FONT.ttf SOME WORDS" or "image MASK.png"
+    #[arg(long)]
+    mask: Option<Mask>,
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Render an SVG without opening a window
+    Render(RenderArgs),
+}
 
 fn window_conf() -> Conf {
     Conf {
@@ -36,14 +104,50 @@ fn window_conf() -> Conf {
     }
 }
 
-#[macroquad::main(window_conf)]
-async fn main() {
+fn main() {
     let res = dotenv::dotenv();
     env_logger::init();
     if let Err(err) = res {
         warn!("{err}")
     };
+
+    let cli = Cli::parse();
+
+    match cli.command {
+        Some(Command::Render(args)) => {
+            if let Err(err) = headless::render(args) {
+                error!("headless render failed: {err}");
+                std::process::exit(1);
+            }
+        }
+        None => {
+            let mut preset = match cli.preset.map(Preset::load).transpose() {
+                Ok(preset) => preset,
+                Err(err) => {
+                    error!("{err}");
+                    std::process::exit(1);
+                }
+            };
+            if let Some(noise_graph) = cli.noise_graph {
+                preset.get_or_insert_with(Preset::default).noise_graph = Some(noise_graph);
+            }
+            if let Some(formula) = cli.formula {
+                preset.get_or_insert_with(Preset::default).use_formula(formula);
+            }
+            if let Some(mask) = cli.mask {
+                preset.get_or_insert_with(Preset::default).mask = Some(mask);
+            }
+
+            macroquad::Window::from_config(window_conf(), run(preset))
+        }
+    }
+}
+
+async fn run(preset: Option<Preset>) {
     let mut state = State::new();
+    if let Some(preset) = preset {
+        state.apply_preset(&preset);
+    }
 
     loop {
         clear_background(BLACK);
@@ -0,0 +1,581 @@
+use crate::consts::{SCREEN_H, SCREEN_W};
+use macroquad::prelude::{Color, Image, ImageFormat};
+use serde::{Deserialize, Serialize};
+use std::{
+    fmt, fs,
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+type Point2<T> = [T; 2];
+
+/// How much of the canvas text is allowed to cover, across and down.
+const TEXT_FILL: f32 = 0.8;
+/// The font size text is measured at before it's scaled to fit the canvas.
+const TEXT_MEASURE_PX: f32 = 100.0;
+/// How far apart samples along a line are when looking for the edges of text and image masks, in
+/// canvas pixels. Half a pixel can't step over a pixel of the mask.
+const RASTER_STEP: f64 = 0.5;
+/// How many times the edge of a text or image mask is halved in on once a line is known to cross
+/// it, which puts it within a thousandth of a pixel.
+const RASTER_BISECTIONS: usize = 10;
+
+/// Which part of an image mask's pixels decides what's inside the mask.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MaskChannel {
+    /// The alpha channel for images with any transparency in them, the luminance for the rest
+    #[default]
+    Auto,
+    /// Opaque pixels are inside the mask
+    Alpha,
+    /// Bright pixels are inside the mask
+    Luminance,
+}
+
+/// The shape a visualization is limited to, in canvas pixels.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "shape", rename_all = "snake_case")]
+pub enum MaskShape {
+    Circle {
+        center: Point2<f64>,
+        radius: f64,
+    },
+    /// Parts that the polygon overlaps itself over an even number of times are outside it
+    Polygon {
+        points: Vec<Point2<f64>>,
+    },
+    /// Text drawn with a TrueType or OpenType font, as big as fits in the middle of the canvas.
+    /// Each line of the text is a line of the mask.
+    Text {
+        text: String,
+        font: PathBuf,
+    },
+    /// A PNG stretched over the whole canvas
+    Image {
+        path: PathBuf,
+        #[serde(default)]
+        channel: MaskChannel,
+    },
+}
+
+/// What a `Mask` is made from, as it's written in presets.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MaskSettings {
+    #[serde(flatten)]
+    pub shape: MaskShape,
+    /// Keep what's outside the shape instead of what's inside it
+    #[serde(default)]
+    pub invert: bool,
+}
+
+/// A region of the canvas that a visualization is limited to. Exports are clipped to it line by
+/// line, so that plotters don't draw anything outside it.
+///
+/// Text and images are read and rasterised when the mask is made, so a mask that's been made is
+/// ready to use. Written like `circle 960 540 400`, `polygon 100,100 1800,200 960,1000`,
+/// `text FONT.ttf SOME WORDS` or `image MASK.png`.
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(try_from = "MaskSettings", into = "MaskSettings")]
+pub struct Mask {
+    settings: MaskSettings,
+    region: Region,
+}
+
+#[derive(Clone)]
+enum Region {
+    Circle(Point2<f64>, f64),
+    Polygon(Vec<Point2<f64>>),
+    /// Whether each pixel of the canvas is inside, row by row
+    Raster(Vec<bool>),
+}
+
+impl Region {
+    fn contains(&self, [x, y]: Point2<f64>) -> bool {
+        match self {
+            Region::Circle([cx, cy], radius) => (x - cx).hypot(y - cy) <= *radius,
+            Region::Polygon(points) => {
+                // Count the edges crossed by a ray going right from the point
+                let mut inside = false;
+                for (i, &[x1, y1]) in points.iter().enumerate() {
+                    let [x2, y2] = points[(i + 1) % points.len()];
+                    if (y1 > y) != (y2 > y) && x < x1 + (y - y1) / (y2 - y1) * (x2 - x1) {
+                        inside = !inside;
+                    }
+                }
+                inside
+            }
+            Region::Raster(pixels) => {
+                if x < 0.0 || y < 0.0 || x >= SCREEN_W as f64 || y >= SCREEN_H as f64 {
+                    return false;
+                }
+                pixels[y as usize * SCREEN_W + x as usize]
+            }
+        }
+    }
+
+    /// How far along the line from `a` to `b` it crosses the edge of the region, as fractions of
+    /// the way from 0 to 1, in order.
+    fn crossings(&self, a: Point2<f64>, b: Point2<f64>) -> Vec<f64> {
+        let [dx, dy] = [b[0] - a[0], b[1] - a[1]];
+        let mut crossings = match self {
+            Region::Circle([cx, cy], radius) => {
+                // Where |a + t(b - a) - c| = radius
+                let [ox, oy] = [a[0] - cx, a[1] - cy];
+                let qa = dx * dx + dy * dy;
+                let qb = 2.0 * (ox * dx + oy * dy);
+                let qc = ox * ox + oy * oy - radius * radius;
+                let discriminant = qb * qb - 4.0 * qa * qc;
+
+                if qa == 0.0 || discriminant <= 0.0 {
+                    vec![]
+                } else {
+                    let root = discriminant.sqrt();
+                    vec![(-qb - root) / (2.0 * qa), (-qb + root) / (2.0 * qa)]
+                }
+            }
+            Region::Polygon(points) => (0..points.len())
+                .filter_map(|i| {
+                    let [x1, y1] = points[i];
+                    let [x2, y2] = points[(i + 1) % points.len()];
+                    let [ex, ey] = [x2 - x1, y2 - y1];
+                    let denominator = dx * ey - dy * ex;
+                    if denominator == 0.0 {
+                        return None;
+                    }
+
+                    let t = ((x1 - a[0]) * ey - (y1 - a[1]) * ex) / denominator;
+                    let u = ((x1 - a[0]) * dy - (y1 - a[1]) * dx) / denominator;
+                    (0.0..=1.0).contains(&u).then_some(t)
+                })
+                .collect(),
+            Region::Raster(_) => {
+                let at = |t: f64| [a[0] + dx * t, a[1] + dy * t];
+                let steps = (dx.hypot(dy) / RASTER_STEP).ceil() as usize;
+                let mut crossings = Vec::new();
+                let mut previous = (0.0, self.contains(a));
+
+                for step in 1..=steps {
+                    let t = step as f64 / steps as f64;
+                    let inside = self.contains(at(t));
+                    if inside != previous.1 {
+                        let (mut low, mut high) = (previous.0, t);
+                        for _ in 0..RASTER_BISECTIONS {
+                            let middle = (low + high) / 2.0;
+                            if self.contains(at(middle)) == previous.1 {
+                                low = middle;
+                            } else {
+                                high = middle;
+                            }
+                        }
+                        crossings.push((low + high) / 2.0);
+                    }
+                    previous = (t, inside);
+                }
+
+                crossings
+            }
+        };
+
+        crossings.retain(|t| *t > 0.0 && *t < 1.0);
+        crossings.sort_by(f64::total_cmp);
+        crossings
+    }
+}
+
+impl Mask {
+    pub fn inverted(&self) -> bool {
+        self.settings.invert
+    }
+
+    pub fn set_inverted(&mut self, invert: bool) {
+        self.settings.invert = invert;
+    }
+
+    /// Whether a point on the canvas is kept.
+    pub fn contains(&self, p: Point2<f64>) -> bool {
+        self.region.contains(p) != self.settings.invert
+    }
+
+    /// Cut the parts of a polyline that are outside the mask out of it, leaving the polylines that
+    /// are inside. Lines are cut exactly where they cross the edge of circles and polygons, and to
+    /// within a thousandth of a pixel for text and images.
+    pub fn clip(&self, points: &[Point2<f64>]) -> Vec<Vec<Point2<f64>>> {
+        let mut polylines = Vec::new();
+        let mut current: Vec<Point2<f64>> = Vec::new();
+
+        for pair in points.windows(2) {
+            let [a, b] = [pair[0], pair[1]];
+            let at = |t: f64| [a[0] + (b[0] - a[0]) * t, a[1] + (b[1] - a[1]) * t];
+
+            let mut cuts = vec![0.0];
+            cuts.extend(self.region.crossings(a, b));
+            cuts.push(1.0);
+
+            for piece in cuts.windows(2) {
+                let [from, to] = [piece[0], piece[1]];
+
+                if self.contains(at((from + to) / 2.0)) {
+                    if current.is_empty() {
+                        current.push(at(from));
+                    }
+                    current.push(at(to));
+                } else if current.len() >= 2 {
+                    polylines.push(std::mem::take(&mut current));
+                } else {
+                    current.clear();
+                }
+            }
+        }
+
+        if current.len() >= 2 {
+            polylines.push(current);
+        }
+
+        polylines
+    }
+
+    /// An image the size of the canvas that's `color` outside the mask and clear inside it, to be
+    /// drawn over a visualization to hide what's outside the mask.
+    pub fn overlay(&self, color: Color) -> Image {
+        let [r, g, b] = [color.r, color.g, color.b].map(|c| (c * 255.0).round() as u8);
+        let mut bytes = Vec::with_capacity(SCREEN_W * SCREEN_H * 4);
+
+        for y in 0..SCREEN_H {
+            for x in 0..SCREEN_W {
+                let alpha = if self.contains([x as f64 + 0.5, y as f64 + 0.5]) {
+                    0
+                } else {
+                    255
+                };
+                bytes.extend_from_slice(&[r, g, b, alpha]);
+            }
+        }
+
+        Image {
+            bytes,
+            width: SCREEN_W as u16,
+            height: SCREEN_H as u16,
+        }
+    }
+}
+
+impl TryFrom<MaskSettings> for Mask {
+    type Error = String;
+
+    fn try_from(settings: MaskSettings) -> Result<Self, Self::Error> {
+        let region = match &settings.shape {
+            MaskShape::Circle { center, radius } if *radius > 0.0 => {
+                Region::Circle(*center, *radius)
+            }
+            MaskShape::Circle { .. } => {
+                return Err("mask circle radius must be more than 0".to_owned());
+            }
+            MaskShape::Polygon { points } if points.len() >= 3 => Region::Polygon(points.clone()),
+            MaskShape::Polygon { .. } => {
+                return Err("mask polygon needs at least 3 points".to_owned());
+            }
+            MaskShape::Text { text, font } => Region::Raster(rasterize_text(text, font)?),
+            MaskShape::Image { path, channel } => Region::Raster(rasterize_image(path, *channel)?),
+        };
+
+        Ok(Self { settings, region })
+    }
+}
+
+impl From<Mask> for MaskSettings {
+    fn from(mask: Mask) -> Self {
+        mask.settings
+    }
+}
+
+impl FromStr for Mask {
+    type Err = String;
+
+    fn from_str(source: &str) -> Result<Self, Self::Err> {
+        let source = source.trim();
+        let (kind, rest) = source
+            .split_once(char::is_whitespace)
+            .unwrap_or((source, ""));
+        let rest = rest.trim();
+        let numbers = || {
+            rest.split(|c: char| c == ',' || c.is_whitespace())
+                .filter(|number| !number.is_empty())
+                .map(|number| {
+                    number
+                        .parse::<f64>()
+                        .map_err(|_| format!("{number} isn't a number"))
+                })
+                .collect::<Result<Vec<_>, _>>()
+        };
+
+        let shape = match kind {
+            "circle" => match numbers()?.as_slice() {
+                &[x, y, radius] => MaskShape::Circle {
+                    center: [x, y],
+                    radius,
+                },
+                _ => return Err("a mask circle is written like circle X Y RADIUS".to_owned()),
+            },
+            "polygon" => {
+                let numbers = numbers()?;
+                if numbers.len() % 2 != 0 {
+                    return Err("mask polygon points need both an x and a y".to_owned());
+                }
+                MaskShape::Polygon {
+                    points: numbers.chunks_exact(2).map(|xy| [xy[0], xy[1]]).collect(),
+                }
+            }
+            "text" => match rest.split_once(char::is_whitespace) {
+                Some((font, text)) => MaskShape::Text {
+                    text: text.trim().to_owned(),
+                    font: font.into(),
+                },
+                None => {
+                    return Err("a mask of text is written like text FONT.ttf SOME WORDS".to_owned());
+                }
+            },
+            "image" if !rest.is_empty() => MaskShape::Image {
+                path: rest.into(),
+                channel: MaskChannel::default(),
+            },
+            "image" => return Err("a mask image is written like image MASK.png".to_owned()),
+            _ => {
+                return Err(format!(
+                    "unknown mask {kind}, expected circle, polygon, text or image"
+                ));
+            }
+        };
+
+        MaskSettings {
+            shape,
+            invert: false,
+        }
+        .try_into()
+    }
+}
+
+impl fmt::Display for Mask {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.settings.shape {
+            MaskShape::Circle {
+                center: [x, y],
+                radius,
+            } => write!(f, "circle of radius {radius} at ({x}, {y})")?,
+            MaskShape::Polygon { points } => write!(f, "polygon of {} points", points.len())?,
+            MaskShape::Text { text, .. } => write!(f, "text {text:?}")?,
+            MaskShape::Image { path, .. } => write!(f, "image {}", path.display())?,
+        }
+
+        if self.settings.invert {
+            write!(f, ", inverted")?;
+        }
+
+        Ok(())
+    }
+}
+
+// The rasterised pixels are left out, they're the same for the same settings
+impl fmt::Debug for Mask {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("Mask").field(&self.settings).finish()
+    }
+}
+
+impl PartialEq for Mask {
+    fn eq(&self, other: &Self) -> bool {
+        self.settings == other.settings
+    }
+}
+
+/// Draw `text` as big as fits in the middle of the canvas, and find which pixels it covers.
+fn rasterize_text(text: &str, font_path: &Path) -> Result<Vec<bool>, String> {
+    let bytes = fs::read(font_path)
+        .map_err(|err| format!("couldn't read mask font {}: {err}", font_path.display()))?;
+    let font = fontdue::Font::from_bytes(bytes, fontdue::FontSettings::default())
+        .map_err(|err| format!("couldn't load mask font {}: {err}", font_path.display()))?;
+
+    let lines: Vec<&str> = text.lines().collect();
+    let line_width = |line: &str, px: f32| -> f32 {
+        line.chars()
+            .map(|c| font.metrics(c, px).advance_width)
+            .sum()
+    };
+    let widest = lines
+        .iter()
+        .map(|line| line_width(line, TEXT_MEASURE_PX))
+        .fold(0.0, f32::max);
+    let Some(line_metrics) = font.horizontal_line_metrics(TEXT_MEASURE_PX) else {
+        return Err(format!(
+            "mask font {} can't be written across",
+            font_path.display()
+        ));
+    };
+    if widest <= 0.0 {
+        return Err("mask text has nothing to draw".to_owned());
+    }
+
+    // Line metrics grow with the font size, so measuring once is enough to scale to fit
+    let tallest = line_metrics.new_line_size * lines.len() as f32;
+    let fit = (SCREEN_W as f32 * TEXT_FILL / widest).min(SCREEN_H as f32 * TEXT_FILL / tallest);
+    let px = TEXT_MEASURE_PX * fit;
+    let ascent = line_metrics.ascent * fit;
+    let line_height = line_metrics.new_line_size * fit;
+
+    let (width, height) = (SCREEN_W, SCREEN_H);
+    let mut pixels = vec![false; width * height];
+    let top = (SCREEN_H as f32 - line_height * lines.len() as f32) / 2.0;
+
+    for (i, line) in lines.iter().enumerate() {
+        let baseline = top + ascent + line_height * i as f32;
+        let mut pen_x = (SCREEN_W as f32 - line_width(line, px)) / 2.0;
+
+        for c in line.chars() {
+            let (metrics, coverage) = font.rasterize(c, px);
+            let left = pen_x.round() as i64 + metrics.xmin as i64;
+            let glyph_top = baseline.round() as i64 - metrics.ymin as i64 - metrics.height as i64;
+
+            for (j, &alpha) in coverage.iter().enumerate() {
+                let x = left + (j % metrics.width) as i64;
+                let y = glyph_top + (j / metrics.width) as i64;
+                if alpha >= 128 && (0..width as i64).contains(&x) && (0..height as i64).contains(&y)
+                {
+                    pixels[y as usize * width + x as usize] = true;
+                }
+            }
+
+            pen_x += metrics.advance_width;
+        }
+    }
+
+    Ok(pixels)
+}
+
+/// Stretch a PNG over the canvas, and find which pixels of it are opaque or bright.
+fn rasterize_image(path: &Path, channel: MaskChannel) -> Result<Vec<bool>, String> {
+    let bytes = fs::read(path)
+        .map_err(|err| format!("couldn't read mask image {}: {err}", path.display()))?;
+    let image = Image::from_file_with_format(&bytes, Some(ImageFormat::Png))
+        .map_err(|err| format!("couldn't load mask image {}: {err}", path.display()))?;
+    let (image_width, image_height) = (image.width as usize, image.height as usize);
+    if image_width == 0 || image_height == 0 {
+        return Err(format!("mask image {} is empty", path.display()));
+    }
+
+    let channel = match channel {
+        MaskChannel::Auto if image.bytes.chunks_exact(4).any(|rgba| rgba[3] < 255) => {
+            MaskChannel::Alpha
+        }
+        MaskChannel::Auto => MaskChannel::Luminance,
+        channel => channel,
+    };
+    let (width, height) = (SCREEN_W, SCREEN_H);
+
+    Ok((0..width * height)
+        .map(|i| {
+            let x = (i % width) * image_width / width;
+            let y = (i / width) * image_height / height;
+            let pixel = (y * image_width + x) * 4;
+            let [r, g, b, a] = [0, 1, 2, 3].map(|channel| image.bytes[pixel + channel] as f32);
+            let value = match channel {
+                MaskChannel::Alpha => a,
+                _ => 0.2126 * r + 0.7152 * g + 0.0722 * b,
+            };
+            value >= 128.0
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_polylines_near(actual: &[Vec<Point2<f64>>], expected: &[Vec<Point2<f64>>]) {
+        let near = |a: &Vec<Point2<f64>>, b: &Vec<Point2<f64>>| {
+            a.len() == b.len()
+                && a.iter().zip(b).all(|(p, q)| (p[0] - q[0]).hypot(p[1] - q[1]) < 1e-3)
+        };
+
+        assert!(
+            actual.len() == expected.len() && actual.iter().zip(expected).all(|(a, b)| near(a, b)),
+            "{actual:?} isn't {expected:?}"
+        );
+    }
+
+    #[test]
+    fn lines_are_cut_where_they_cross_a_circle() {
+        let mask: Mask = "circle 500 500 100".parse().unwrap();
+
+        assert_polylines_near(
+            &mask.clip(&[[300.0, 500.0], [500.0, 500.0], [500.0, 300.0]]),
+            &[vec![[400.0, 500.0], [500.0, 500.0], [500.0, 400.0]]],
+        );
+    }
+
+    #[test]
+    fn lines_are_cut_where_they_cross_a_polygon() {
+        let mask: Mask = "polygon 100,100 200,100 200,200 100,200".parse().unwrap();
+
+        // In and out twice through the same segment's ends
+        assert_polylines_near(
+            &mask.clip(&[[50.0, 150.0], [150.0, 150.0], [150.0, 250.0], [150.0, 150.0]]),
+            &[
+                vec![[100.0, 150.0], [150.0, 150.0], [150.0, 200.0]],
+                vec![[150.0, 200.0], [150.0, 150.0]],
+            ],
+        );
+    }
+
+    #[test]
+    fn lines_inside_are_kept_and_outside_dropped() {
+        let mask: Mask = "circle 500 500 100".parse().unwrap();
+        let inside = vec![[450.0, 500.0], [550.0, 500.0], [500.0, 450.0]];
+
+        assert_eq!(mask.clip(&inside), vec![inside]);
+        assert!(mask.clip(&[[0.0, 0.0], [100.0, 0.0], [100.0, 100.0]]).is_empty());
+        // Passing by without going in
+        assert!(mask.clip(&[[350.0, 0.0], [350.0, 1000.0]]).is_empty());
+    }
+
+    #[test]
+    fn inverted_masks_keep_the_outside() {
+        let mut mask: Mask = "circle 500 500 100".parse().unwrap();
+        mask.set_inverted(true);
+
+        assert_polylines_near(
+            &mask.clip(&[[300.0, 500.0], [700.0, 500.0]]),
+            &[
+                vec![[300.0, 500.0], [400.0, 500.0]],
+                vec![[600.0, 500.0], [700.0, 500.0]],
+            ],
+        );
+        assert!(mask.clip(&[[450.0, 500.0], [550.0, 500.0]]).is_empty());
+    }
+
+    #[test]
+    fn lines_are_cut_at_a_raster_edge() {
+        // The left half of the canvas
+        let pixels = (0..SCREEN_W * SCREEN_H).map(|i| i % SCREEN_W < 960).collect();
+        let mut mask = Mask {
+            settings: MaskSettings {
+                shape: MaskShape::Image {
+                    path: "left-half.png".into(),
+                    channel: MaskChannel::Auto,
+                },
+                invert: false,
+            },
+            region: Region::Raster(pixels),
+        };
+
+        assert_polylines_near(
+            &mask.clip(&[[900.3, 100.0], [1000.3, 200.0]]),
+            &[vec![[900.3, 100.0], [960.0, 159.7]]],
+        );
+
+        mask.set_inverted(true);
+        assert_polylines_near(
+            &mask.clip(&[[900.3, 100.0], [1000.3, 200.0]]),
+            &[vec![[960.0, 159.7], [1000.3, 200.0]]],
+        );
+    }
+}
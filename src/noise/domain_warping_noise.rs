@@ -1,54 +1,193 @@
+use super::{generator_by_index, FractalParams, Generator, NOISE_FN_COUNT, NOISE_FN_IDENTIFIERS};
+use crate::settings::Choice;
 use log::trace;
-use noise::{Fbm, NoiseFn};
+use noise::NoiseFn;
 use rand::{prelude::StdRng, Rng, SeedableRng};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::fmt;
 
-pub struct DomainWarpingNoise<T> {
-    fbm: Fbm<T>,
+/// Fractal Brownian motion, which domain warping has always been built from
+const DEFAULT_BASE: usize = 3;
+const DEFAULT_DEPTH: usize = 2;
+const MAX_DEPTH: usize = 8;
+
+pub struct DomainWarpingNoise {
+    base: Box<dyn Generator>,
     params: DomainWarpingNoiseParams,
 }
 
-impl<T: Default + noise::Seedable> DomainWarpingNoise<T> {
-    pub fn new(params: DomainWarpingNoiseParams) -> Self {
+impl DomainWarpingNoise {
+    /// Warp the generator chosen by `params`. `fractal` is passed on to it if it's fractal, and
+    /// otherwise it uses its own defaults.
+    pub fn new(params: DomainWarpingNoiseParams, seed: u32, fractal: Option<&FractalParams>) -> Self {
+        let fractal = fractal
+            .copied()
+            .or_else(|| FractalParams::default_for_index(params.base));
+
         Self {
-            fbm: Fbm::new(0),
+            base: generator_by_index(params.base, seed, fractal.as_ref(), None),
             params,
         }
     }
+}
 
-    pub fn inigo() -> Self {
-        Self {
-            fbm: Fbm::new(0),
-            params: DomainWarpingNoiseParams::inigo(),
+/// The starting points that `DomainWarpingNoiseParams` can be switched between.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WarpPreset {
+    Default,
+    Inigo,
+    /// `DomainWarpingNoiseParams::random` with the scene's seed
+    Random,
+}
+
+impl WarpPreset {
+    pub fn params(self, seed: u32) -> DomainWarpingNoiseParams {
+        match self {
+            WarpPreset::Default => DomainWarpingNoiseParams::default(),
+            WarpPreset::Inigo => DomainWarpingNoiseParams::inigo(),
+            WarpPreset::Random => DomainWarpingNoiseParams::random(seed),
         }
     }
 }
 
-impl<T: Default + noise::Seedable> Default for DomainWarpingNoise<T> {
-    fn default() -> Self {
-        Self {
-            fbm: Fbm::new(0),
-            params: Default::default(),
+impl Choice for WarpPreset {
+    const ALL: &'static [Self] = &[WarpPreset::Default, WarpPreset::Inigo, WarpPreset::Random];
+}
+
+impl fmt::Display for WarpPreset {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            WarpPreset::Default => write!(f, "default"),
+            WarpPreset::Inigo => write!(f, "inigo"),
+            WarpPreset::Random => write!(f, "random"),
         }
     }
 }
 
-#[derive(Debug)]
+/// The generator being warped, how many times it warps itself, and the offsets and strengths of
+/// each warp stage. `q` and `r` each hold three xyz offsets, one per axis of the warp, and `qn` and
+/// `rn` scale how far each stage warps. Stages past the second take turns using `q` and `r` again.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct DomainWarpingNoiseParams {
+    /// The index of the generator being warped, saved by its name in `NOISE_FN_IDENTIFIERS`
+    #[serde(default = "default_base", with = "base_identifier")]
+    base: usize,
+    #[serde(default = "default_depth")]
+    depth: usize,
     q: [f64; 9],
     r: [f64; 9],
     qn: f64,
     rn: f64,
 }
 
+fn default_base() -> usize {
+    DEFAULT_BASE
+}
+
+fn default_depth() -> usize {
+    DEFAULT_DEPTH
+}
+
 impl DomainWarpingNoiseParams {
-    pub fn new(q: [f64; 9], r: [f64; 9], qn: f64, rn: f64) -> Self {
-        Self { q, r, qn, rn }
+    /// How many fields `field_name`, `field_value` and `nudge` know about: the base generator, the
+    /// depth, `qn`, `rn`, then every offset in `q` and `r`.
+    pub const FIELD_COUNT: usize = 4 + 9 + 9;
+
+    /// The index of the generator being warped.
+    pub fn base(&self) -> usize {
+        self.base
+    }
+
+    /// Which preset these parameters' offsets and strengths match, if any.
+    pub fn preset(&self, seed: u32) -> Option<WarpPreset> {
+        WarpPreset::ALL.iter().copied().find(|preset| {
+            let params = preset.params(seed);
+            (params.q, params.r, params.qn, params.rn) == (self.q, self.r, self.qn, self.rn)
+        })
+    }
+
+    /// Take the offsets and strengths of `preset`, keeping the base generator and depth.
+    pub fn apply_preset(&mut self, preset: WarpPreset, seed: u32) {
+        *self = DomainWarpingNoiseParams {
+            base: self.base,
+            depth: self.depth,
+            ..preset.params(seed)
+        };
+    }
+
+    /// The parameters the generator at `index` starts with, or `None` if it doesn't warp.
+    pub fn for_index(index: usize, seed: u32) -> Option<Self> {
+        match index {
+            0 => Some(Self::random(seed)),
+            _ => None,
+        }
+    }
+
+    /// Replace the offsets with random ones from `seed`, keeping the warp strengths.
+    pub fn reroll_offsets(&mut self, seed: u32) {
+        let DomainWarpingNoiseParams { q, r, .. } = Self::random(seed);
+        self.q = q;
+        self.r = r;
+    }
+
+    pub fn field_name(field: usize) -> String {
+        match field {
+            0 => "warp base".to_owned(),
+            1 => "warp depth".to_owned(),
+            2 => "warp qn".to_owned(),
+            3 => "warp rn".to_owned(),
+            4..=12 => format!("warp q[{}]", field - 4),
+            13..=21 => format!("warp r[{}]", field - 13),
+            _ => unreachable!(),
+        }
+    }
+
+    pub fn field_value(&self, field: usize) -> String {
+        match field {
+            0 => NOISE_FN_IDENTIFIERS[self.base].to_owned(),
+            1 => self.depth.to_string(),
+            _ => format!("{:.2}", *self.clone().field(field)),
+        }
+    }
+
+    pub fn nudge(&mut self, field: usize, increase: bool) {
+        let sign = if increase { 1.0 } else { -1.0 };
+
+        match field {
+            // Skip over domain warping noise itself, which can't warp itself
+            0 => {
+                let bases = NOISE_FN_COUNT - 1;
+                let base = self.base - 1;
+                self.base = 1 + if increase { (base + 1) % bases } else { (base + bases - 1) % bases };
+            }
+            1 => {
+                self.depth = if increase {
+                    (self.depth + 1).min(MAX_DEPTH)
+                } else {
+                    self.depth.saturating_sub(1)
+                }
+            }
+            // Negative strengths would only flip the warp around
+            2 | 3 => *self.field(field) = (*self.field(field) + sign * 0.1).max(0.0),
+            _ => *self.field(field) += sign * 0.25,
+        }
+    }
+
+    fn field(&mut self, field: usize) -> &mut f64 {
+        match field {
+            2 => &mut self.qn,
+            3 => &mut self.rn,
+            4..=12 => &mut self.q[field - 4],
+            13..=21 => &mut self.r[field - 13],
+            _ => unreachable!(),
+        }
     }
 
-    pub fn random() -> Self {
+    /// Create random parameters. The same seed always produces the same parameters.
+    pub fn random(seed: u32) -> Self {
         // rng.gen handles arrays just fine but fills them with numbers between 0.0 and 1.0
         // gen_range allows for numbers from a larger range, but it can't handle arrays :(
-        let mut rng = StdRng::from_rng(&mut rand::rng());
+        let mut rng = StdRng::seed_from_u64(seed as u64);
         let q_range = -100.0..100.0;
         let q: [f64; 9] = [
             rng.random_range(q_range.clone()),
@@ -77,7 +216,14 @@ impl DomainWarpingNoiseParams {
         let qn: f64 = rng.random_range(0.001..5.0);
         let rn: f64 = rng.random_range(0.001..5.0);
 
-        let s = Self { q, r, qn, rn };
+        let s = Self {
+            base: DEFAULT_BASE,
+            depth: DEFAULT_DEPTH,
+            q,
+            r,
+            qn,
+            rn,
+        };
 
         trace!(
             "Creating random parameters for domain warping noise:\n{:?}",
@@ -88,8 +234,10 @@ impl DomainWarpingNoiseParams {
     }
 
     // https://www.iquilezles.org/www/articles/warp/warp.htm
-    pub(crate) fn inigo() -> Self {
+    pub fn inigo() -> Self {
         Self {
+            base: DEFAULT_BASE,
+            depth: DEFAULT_DEPTH,
             q: [0.0, 0.0, 0.0, 5.2, 1.3, 0.0, 0.0, 0.0, 0.0],
             r: [1.7, 9.2, 0.0, 8.3, 2.8, 0.0, 0.0, 0.0, 0.0],
             qn: 4.0,
@@ -101,6 +249,8 @@ impl DomainWarpingNoiseParams {
 impl Default for DomainWarpingNoiseParams {
     fn default() -> Self {
         Self {
+            base: DEFAULT_BASE,
+            depth: DEFAULT_DEPTH,
             q: Default::default(),
             r: Default::default(),
             qn: 1.0,
@@ -109,29 +259,85 @@ impl Default for DomainWarpingNoiseParams {
     }
 }
 
-// Image for f(p) = fbm(p+fbm(p+fbm(p)))
-impl<T> NoiseFn<f64, 3> for DomainWarpingNoise<T>
-where
-T: NoiseFn<f64, 3>,{
+impl DomainWarpingNoise {
+    // Image for f(p) = fbm(p+fbm(p+fbm(p))) at the default depth of 2. Only x, y and z are warped,
+    // so a 4th dimension passes straight through.
+    fn sample<const D: usize>(&self, p: [f64; D]) -> f64
+    where
+        dyn Generator: NoiseFn<f64, D>,
+    {
+        let mut warp = vec3(0.0, 0.0, 0.0);
+
+        for stage in 0..self.params.depth {
+            let (offsets, n) = match stage % 2 {
+                0 => (self.params.q, self.params.qn),
+                _ => (self.params.r, self.params.rn),
+            };
+            let [o0, o1, o2, o3, o4, o5, o6, o7, o8] = offsets;
+            let next = vec3(
+                self.base.get(shift(p, add(warp, vec3(o0, o1, o2)))),
+                self.base.get(shift(p, add(warp, vec3(o3, o4, o5)))),
+                self.base.get(shift(p, add(warp, vec3(o6, o7, o8)))),
+            );
+
+            warp = mul_n(next, n);
+        }
+
+        self.base.get(shift(p, warp))
+    }
+}
+
+impl NoiseFn<f64, 3> for DomainWarpingNoise {
     fn get(&self, p: [f64; 3]) -> f64 {
-        let [q0, q1, q2, q3, q4, q5, q6, q7, q8] = self.params.q;
-        let q = vec3(
-            self.fbm.get(add(p, vec3(q0, q1, q2))),
-            self.fbm.get(add(p, vec3(q3, q4, q5))),
-            self.fbm.get(add(p, vec3(q6, q7, q8))),
-        );
+        self.sample(p)
+    }
+}
 
-        let q = mul_n(q, self.params.qn);
+impl NoiseFn<f64, 4> for DomainWarpingNoise {
+    fn get(&self, p: [f64; 4]) -> f64 {
+        self.sample(p)
+    }
+}
 
-        let [r0, r1, r2, r3, r4, r5, r6, r7, r8] = self.params.r;
-        let r = vec3(
-            self.fbm.get(add(p, add(q, vec3(r0, r1, r2)))),
-            self.fbm.get(add(p, add(q, vec3(r3, r4, r5)))),
-            self.fbm.get(add(p, add(q, vec3(r6, r7, r8)))),
-        );
+/// Writes every parameter on one line, like `warp.base=fbm warp.depth=2 warp.qn=1 ...`, named as
+/// they are in presets, since re-rolled offsets can't be recreated from the seed.
+impl fmt::Display for DomainWarpingNoiseParams {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let list = |values: &[f64]| {
+            let values: Vec<String> = values.iter().map(f64::to_string).collect();
+            format!("[{}]", values.join(","))
+        };
+
+        write!(
+            f,
+            "warp.base={} warp.depth={} warp.qn={} warp.rn={} warp.q={} warp.r={}",
+            NOISE_FN_IDENTIFIERS[self.base],
+            self.depth,
+            self.qn,
+            self.rn,
+            list(&self.q),
+            list(&self.r)
+        )
+    }
+}
+
+/// Saves the base generator by name so that presets don't depend on the order of the generators.
+mod base_identifier {
+    use super::{NOISE_FN_IDENTIFIERS, Deserialize, Deserializer, Serializer};
+    use serde::de::Error;
 
-        let r = mul_n(r, self.params.rn);
-        self.fbm.get(add(p, r))
+    pub fn serialize<S: Serializer>(base: &usize, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(NOISE_FN_IDENTIFIERS[*base])
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<usize, D::Error> {
+        let name = String::deserialize(deserializer)?;
+
+        match NOISE_FN_IDENTIFIERS.iter().position(|&id| id == name) {
+            Some(0) => Err(D::Error::custom("domain warping noise can't warp itself")),
+            Some(index) => Ok(index),
+            None => Err(D::Error::custom(format!("unknown noise generator `{name}`"))),
+        }
     }
 }
 
@@ -146,6 +352,15 @@ fn add(xyz_1: [f64; 3], xyz_2: [f64; 3]) -> [f64; 3] {
     [x1 + x2, y1 + y2, z1 + z2]
 }
 
+/// Move the x, y and z of `p` by `xyz`.
+fn shift<const D: usize>(mut p: [f64; D], xyz: [f64; 3]) -> [f64; D] {
+    for (p, offset) in p.iter_mut().zip(xyz) {
+        *p += offset;
+    }
+
+    p
+}
+
 fn mul_n(xyz: [f64; 3], n: f64) -> [f64; 3] {
     let [x, y, z] = xyz;
 
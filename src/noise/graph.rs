@@ -0,0 +1,904 @@
+use super::{generator_by_index, FractalParams, Generator, LoopingNoise, NOISE_FN_IDENTIFIERS};
+use noise::{NoiseFn, Perlin, Seedable};
+use serde::{Deserialize, Serialize};
+use std::{fmt, str::FromStr};
+
+/// A noise built by combining other noises, written like
+/// `Blend(Perlin, Worley, control = Fbm(octaves = 3))`.
+///
+/// Generators take the same names as in formulas, in either `snake_case` or `CamelCase`, and
+/// accept `seed` (added to the scene's seed) plus the fractal settings if they're fractal.
+/// Sources and settings can be passed in order or by name.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct NoiseGraph {
+    root: Node,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Node {
+    Generator {
+        index: usize,
+        /// Added to the scene's seed so that two of the same generator can differ
+        seed: u32,
+        fractal: Option<FractalParams>,
+    },
+    Op {
+        op: Op,
+        sources: Vec<Node>,
+        params: Vec<f64>,
+    },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Op {
+    Constant,
+    Add,
+    Multiply,
+    Min,
+    Max,
+    Power,
+    Blend,
+    Select,
+    Turbulence,
+    ScaleBias,
+    Abs,
+    Negate,
+    Clamp,
+    Exponent,
+}
+
+/// A number an `Op` can be tuned with.
+struct Param {
+    name: &'static str,
+    default: f64,
+    /// How much a nudge adds, or multiplies by if `scaled`
+    step: f64,
+    scaled: bool,
+    min: f64,
+    max: f64,
+}
+
+impl Param {
+    const fn by(name: &'static str, default: f64, step: f64) -> Self {
+        Self {
+            name,
+            default,
+            step,
+            scaled: false,
+            min: f64::NEG_INFINITY,
+            max: f64::INFINITY,
+        }
+    }
+
+    const fn times(name: &'static str, default: f64) -> Self {
+        Self {
+            scaled: true,
+            min: 0.0,
+            ..Self::by(name, default, 1.1)
+        }
+    }
+
+    const fn count(name: &'static str, default: f64, max: f64) -> Self {
+        Self {
+            min: 0.0,
+            max,
+            ..Self::by(name, default, 1.0)
+        }
+    }
+
+    fn nudged(&self, value: f64, increase: bool) -> f64 {
+        let value = match (self.scaled, increase) {
+            (true, true) => value * self.step,
+            (true, false) => value / self.step,
+            (false, true) => value + self.step,
+            (false, false) => value - self.step,
+        };
+
+        value.clamp(self.min, self.max)
+    }
+
+    fn format(&self, value: f64) -> String {
+        if self.step == 1.0 && !self.scaled {
+            format!("{value:.0}")
+        } else {
+            format!("{value:.3}")
+        }
+    }
+}
+
+const CONSTANT_PARAMS: &[Param] = &[Param::by("value", 0.0, 0.05)];
+const SELECT_PARAMS: &[Param] = &[
+    Param::by("lower", 0.0, 0.05),
+    Param::by("upper", 1.0, 0.05),
+    Param {
+        min: 0.0,
+        ..Param::by("falloff", 0.0, 0.05)
+    },
+];
+const TURBULENCE_PARAMS: &[Param] = &[
+    Param::times("frequency", 1.0),
+    Param::times("power", 1.0),
+    Param {
+        min: 1.0,
+        ..Param::count("roughness", 3.0, 32.0)
+    },
+    Param::count("seed", 0.0, u32::MAX as f64),
+];
+const SCALE_BIAS_PARAMS: &[Param] = &[Param::by("scale", 1.0, 0.05), Param::by("bias", 0.0, 0.05)];
+const CLAMP_PARAMS: &[Param] = &[Param::by("lower", -1.0, 0.05), Param::by("upper", 1.0, 0.05)];
+const EXPONENT_PARAMS: &[Param] = &[Param::by("exponent", 1.0, 0.1)];
+
+impl Op {
+    const ALL: [Op; 14] = [
+        Op::Constant,
+        Op::Add,
+        Op::Multiply,
+        Op::Min,
+        Op::Max,
+        Op::Power,
+        Op::Blend,
+        Op::Select,
+        Op::Turbulence,
+        Op::ScaleBias,
+        Op::Abs,
+        Op::Negate,
+        Op::Clamp,
+        Op::Exponent,
+    ];
+
+    fn name(self) -> &'static str {
+        match self {
+            Op::Constant => "Constant",
+            Op::Add => "Add",
+            Op::Multiply => "Multiply",
+            Op::Min => "Min",
+            Op::Max => "Max",
+            Op::Power => "Power",
+            Op::Blend => "Blend",
+            Op::Select => "Select",
+            Op::Turbulence => "Turbulence",
+            Op::ScaleBias => "ScaleBias",
+            Op::Abs => "Abs",
+            Op::Negate => "Negate",
+            Op::Clamp => "Clamp",
+            Op::Exponent => "Exponent",
+        }
+    }
+
+    fn sources(self) -> &'static [&'static str] {
+        match self {
+            Op::Constant => &[],
+            Op::Add | Op::Multiply | Op::Min | Op::Max | Op::Power => &["a", "b"],
+            Op::Blend | Op::Select => &["a", "b", "control"],
+            Op::Turbulence | Op::ScaleBias | Op::Abs | Op::Negate | Op::Clamp | Op::Exponent => {
+                &["source"]
+            }
+        }
+    }
+
+    fn params(self) -> &'static [Param] {
+        match self {
+            Op::Constant => CONSTANT_PARAMS,
+            Op::Select => SELECT_PARAMS,
+            Op::Turbulence => TURBULENCE_PARAMS,
+            Op::ScaleBias => SCALE_BIAS_PARAMS,
+            Op::Clamp => CLAMP_PARAMS,
+            Op::Exponent => EXPONENT_PARAMS,
+            Op::Add | Op::Multiply | Op::Min | Op::Max | Op::Power => &[],
+            Op::Blend | Op::Abs | Op::Negate => &[],
+        }
+    }
+}
+
+impl Node {
+    fn name(&self) -> String {
+        match self {
+            Node::Generator { index, .. } => camel_case(NOISE_FN_IDENTIFIERS[*index]),
+            Node::Op { op, .. } => op.name().to_owned(),
+        }
+    }
+
+    /// Build the node as `D` dimensional noise, which is 3 normally or 4 for looping animations.
+    fn build<const D: usize>(&self, scene_seed: u32) -> Box<dyn NoiseFn<f64, D>>
+    where
+        Box<dyn Generator>: NoiseFn<f64, D>,
+        noise::Turbulence<Box<dyn NoiseFn<f64, D>>, Perlin>: NoiseFn<f64, D>,
+    {
+        let (op, sources, params) = match self {
+            Node::Generator {
+                index,
+                seed,
+                fractal,
+            } => {
+                let seed = scene_seed.wrapping_add(*seed);
+                return Box::new(generator_by_index(*index, seed, fractal.as_ref(), None));
+            }
+            Node::Op {
+                op,
+                sources,
+                params,
+            } => (*op, sources, params),
+        };
+        let mut sources = sources.iter().map(|source| source.build(scene_seed));
+        let mut source = || sources.next().expect("the parser checks every source is there");
+
+        match op {
+            Op::Constant => Box::new(noise::Constant::new(params[0])),
+            Op::Add => Box::new(noise::Add::new(source(), source())),
+            Op::Multiply => Box::new(noise::Multiply::new(source(), source())),
+            Op::Min => Box::new(noise::Min::new(source(), source())),
+            Op::Max => Box::new(noise::Max::new(source(), source())),
+            Op::Power => Box::new(noise::Power::new(source(), source())),
+            Op::Blend => Box::new(noise::Blend::new(source(), source(), source())),
+            Op::Select => Box::new(
+                noise::Select::new(source(), source(), source())
+                    .set_bounds(params[0], params[1])
+                    .set_falloff(params[2]),
+            ),
+            Op::Turbulence => Box::new(
+                noise::Turbulence::<_, Perlin>::new(source())
+                    .set_seed(scene_seed.wrapping_add(params[3] as u32))
+                    .set_frequency(params[0])
+                    .set_power(params[1])
+                    .set_roughness(params[2] as usize),
+            ),
+            Op::ScaleBias => Box::new(
+                noise::ScaleBias::new(source())
+                    .set_scale(params[0])
+                    .set_bias(params[1]),
+            ),
+            Op::Abs => Box::new(noise::Abs::new(source())),
+            Op::Negate => Box::new(noise::Negate::new(source())),
+            Op::Clamp => Box::new(noise::Clamp::new(source()).set_bounds(params[0], params[1])),
+            Op::Exponent => Box::new(noise::Exponent::new(source()).set_exponent(params[0])),
+        }
+    }
+
+    /// How many settings this node has, not counting its sources.
+    fn param_count(&self) -> usize {
+        match self {
+            Node::Generator { fractal, .. } => match fractal {
+                Some(_) => FractalParams::FIELD_NAMES.len() + 1,
+                None => 1,
+            },
+            Node::Op { params, .. } => params.len(),
+        }
+    }
+
+    fn param_name(&self, param: usize) -> &'static str {
+        match self {
+            Node::Generator { fractal, .. } => match fractal {
+                Some(_) if param < FractalParams::FIELD_NAMES.len() => {
+                    FractalParams::FIELD_NAMES[param]
+                }
+                _ => "seed",
+            },
+            Node::Op { op, .. } => op.params()[param].name,
+        }
+    }
+
+    fn param_value(&self, param: usize) -> String {
+        match self {
+            Node::Generator { seed, fractal, .. } => match fractal {
+                Some(fractal) if param < FractalParams::FIELD_NAMES.len() => {
+                    fractal.field_value(param)
+                }
+                _ => seed.to_string(),
+            },
+            Node::Op { op, params, .. } => op.params()[param].format(params[param]),
+        }
+    }
+
+    fn nudge_param(&mut self, param: usize, increase: bool) {
+        match self {
+            Node::Generator { seed, fractal, .. } => match fractal {
+                Some(fractal) if param < FractalParams::FIELD_NAMES.len() => {
+                    fractal.nudge(param, increase)
+                }
+                _ if increase => *seed = seed.saturating_add(1),
+                _ => *seed = seed.saturating_sub(1),
+            },
+            Node::Op { op, params, .. } => {
+                params[param] = op.params()[param].nudged(params[param], increase)
+            }
+        }
+    }
+
+    /// Every node in the graph, parents before their sources.
+    fn nodes(&self) -> Vec<&Node> {
+        let mut nodes = vec![self];
+        if let Node::Op { sources, .. } = self {
+            nodes.extend(sources.iter().flat_map(Node::nodes));
+        }
+
+        nodes
+    }
+
+    /// The node that the graph's `param`th setting belongs to, and which of its settings it is.
+    /// Otherwise, how many settings are left over after this node and its sources.
+    fn param_mut(&mut self, param: usize) -> Result<(&mut Node, usize), usize> {
+        let count = self.param_count();
+        if param < count {
+            return Ok((self, param));
+        }
+
+        let mut param = param - count;
+        if let Node::Op { sources, .. } = self {
+            for source in sources {
+                match source.param_mut(param) {
+                    Ok(found) => return Ok(found),
+                    Err(remaining) => param = remaining,
+                }
+            }
+        }
+
+        Err(param)
+    }
+}
+
+impl NoiseGraph {
+    /// Build the graph's noise. With a `loop_length`, time goes around a `LoopingNoise` of that
+    /// length instead of on forever.
+    pub fn build(&self, seed: u32, loop_length: Option<f64>) -> Box<dyn NoiseFn<f64, 3>> {
+        log::info!("now using {self} noise graph");
+
+        match loop_length {
+            Some(loop_length) => Box::new(LoopingNoise::new(self.root.build::<4>(seed), loop_length)),
+            None => self.root.build(seed),
+        }
+    }
+
+    /// How many settings the graph has across all of its nodes.
+    pub fn param_count(&self) -> usize {
+        self.root.nodes().iter().map(|node| node.param_count()).sum()
+    }
+
+    /// The node that setting `param` belongs to, its number in the graph (counting from 1, parents
+    /// first) and which of the node's settings it is.
+    fn find_param(&self, mut param: usize) -> (&Node, usize, usize) {
+        for (i, node) in self.root.nodes().into_iter().enumerate() {
+            let count = node.param_count();
+            if param < count {
+                return (node, i + 1, param);
+            }
+            param -= count;
+        }
+
+        unreachable!("settings are counted by param_count")
+    }
+
+    /// The name of setting `param`, like "2 Worley seed".
+    pub fn param_name(&self, param: usize) -> String {
+        let (node, number, param) = self.find_param(param);
+
+        format!("{number} {} {}", node.name(), node.param_name(param))
+    }
+
+    pub fn param_value(&self, param: usize) -> String {
+        let (node, _, param) = self.find_param(param);
+
+        node.param_value(param)
+    }
+
+    pub fn nudge_param(&mut self, param: usize, increase: bool) {
+        let (node, param) = self
+            .root
+            .param_mut(param)
+            .expect("settings are counted by param_count");
+        node.nudge_param(param, increase);
+    }
+}
+
+fn camel_case(identifier: &str) -> String {
+    identifier
+        .split('_')
+        .map(|word| {
+            let mut chars = word.chars();
+            chars
+                .next()
+                .map(|first| first.to_ascii_uppercase().to_string() + chars.as_str())
+                .unwrap_or_default()
+        })
+        .collect()
+}
+
+/// Names are matched without regard to case or underscores, so `open_simplex` and `OpenSimplex`
+/// are the same.
+fn same_name(a: &str, b: &str) -> bool {
+    let normalize = |name: &str| name.replace('_', "").to_ascii_lowercase();
+    normalize(a) == normalize(b)
+}
+
+impl fmt::Display for Node {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // Only arguments that differ from their defaults are written out
+        let mut args: Vec<String> = vec![];
+        match self {
+            Node::Generator { index, seed, fractal } => {
+                let default_fractal = FractalParams::default_for_index(*index);
+                if let (Some(fractal), Some(default_fractal)) = (fractal, default_fractal) {
+                    let fields = [
+                        (fractal.octaves as f64, default_fractal.octaves as f64),
+                        (fractal.frequency, default_fractal.frequency),
+                        (fractal.lacunarity, default_fractal.lacunarity),
+                        (fractal.persistence, default_fractal.persistence),
+                    ];
+                    for (name, (value, default)) in FractalParams::FIELD_NAMES.iter().zip(fields) {
+                        if value != default {
+                            args.push(format!("{name} = {value}"));
+                        }
+                    }
+                }
+                if *seed != 0 {
+                    args.push(format!("seed = {seed}"));
+                }
+            }
+            Node::Op {
+                op,
+                sources,
+                params,
+            } => {
+                args.extend(sources.iter().map(Node::to_string));
+                for (param, value) in op.params().iter().zip(params) {
+                    if *value != param.default {
+                        args.push(format!("{} = {value}", param.name));
+                    }
+                }
+            }
+        }
+
+        write!(f, "{}", self.name())?;
+        if !args.is_empty() {
+            write!(f, "({})", args.join(", "))?;
+        }
+
+        Ok(())
+    }
+}
+
+impl fmt::Display for NoiseGraph {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.root)
+    }
+}
+
+impl FromStr for NoiseGraph {
+    type Err = String;
+
+    fn from_str(source: &str) -> Result<Self, Self::Err> {
+        let mut parser = Parser {
+            tokens: tokenize(source)?,
+            position: 0,
+            depth: 0,
+        };
+        let root = parser.node()?;
+        if let Some(token) = parser.tokens.get(parser.position) {
+            return Err(format!("unexpected {token} after the end of the noise graph"));
+        }
+
+        Ok(Self { root })
+    }
+}
+
+impl TryFrom<String> for NoiseGraph {
+    type Error = String;
+
+    fn try_from(source: String) -> Result<Self, Self::Error> {
+        source.parse()
+    }
+}
+
+impl From<NoiseGraph> for String {
+    fn from(graph: NoiseGraph) -> Self {
+        graph.to_string()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Name(String),
+    Number(f64),
+    Punct(char),
+}
+
+impl fmt::Display for Token {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Token::Name(name) => write!(f, "`{name}`"),
+            Token::Number(value) => write!(f, "`{value}`"),
+            Token::Punct(c) => write!(f, "`{c}`"),
+        }
+    }
+}
+
+fn tokenize(source: &str) -> Result<Vec<Token>, String> {
+    let mut tokens = vec![];
+    let mut chars = source.char_indices().peekable();
+
+    while let Some(&(start, c)) = chars.peek() {
+        if c.is_whitespace() {
+            chars.next();
+        } else if "(),=".contains(c) {
+            tokens.push(Token::Punct(c));
+            chars.next();
+        } else if c.is_alphanumeric() || "_.-+".contains(c) {
+            let mut end = start;
+            while let Some(&(i, c)) = chars.peek() {
+                if !(c.is_alphanumeric() || "_.-+".contains(c)) {
+                    break;
+                }
+                end = i + c.len_utf8();
+                chars.next();
+            }
+            let word = &source[start..end];
+            let token = match word.parse() {
+                Ok(value) => Token::Number(value),
+                Err(_) if c.is_alphabetic() => Token::Name(word.to_owned()),
+                Err(_) => return Err(format!("`{word}` isn't a number")),
+            };
+            tokens.push(token);
+        } else {
+            return Err(format!("unexpected character `{c}` in noise graph"));
+        }
+    }
+
+    Ok(tokens)
+}
+
+/// A value passed to a node, before it's known whether it's a source or a setting.
+enum Arg {
+    Node(Node),
+    Number(f64),
+}
+
+/// How deeply nodes can be nested inside each other, so that a long enough graph can't overflow
+/// the stack.
+const MAX_NESTING: usize = 200;
+
+struct Parser {
+    tokens: Vec<Token>,
+    position: usize,
+    /// How many nodes deep the parser is
+    depth: usize,
+}
+
+impl Parser {
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.position).cloned();
+        self.position += 1;
+        token
+    }
+
+    fn peek_is(&self, c: char) -> bool {
+        self.tokens.get(self.position) == Some(&Token::Punct(c))
+    }
+
+    fn node(&mut self) -> Result<Node, String> {
+        if self.depth >= MAX_NESTING {
+            return Err(format!("noise graph is nested more than {MAX_NESTING} deep"));
+        }
+        self.depth += 1;
+        let node = self.call()?;
+        self.depth -= 1;
+
+        Ok(node)
+    }
+
+    /// A noise's name, followed by its arguments in brackets if it has any.
+    fn call(&mut self) -> Result<Node, String> {
+        let name = match self.next() {
+            Some(Token::Name(name)) => name,
+            Some(token) => return Err(format!("expected a noise but found {token}")),
+            None => return Err("noise graph ended early".to_owned()),
+        };
+
+        let mut positional = vec![];
+        let mut named = vec![];
+        if self.peek_is('(') {
+            self.position += 1;
+            while !self.peek_is(')') {
+                let is_named = matches!(self.tokens.get(self.position), Some(Token::Name(_)))
+                    && self.tokens.get(self.position + 1) == Some(&Token::Punct('='));
+                if is_named {
+                    let Some(Token::Name(arg_name)) = self.next() else {
+                        unreachable!()
+                    };
+                    self.position += 1;
+                    named.push((arg_name, self.arg()?));
+                } else {
+                    positional.push(self.arg()?);
+                }
+
+                if self.peek_is(',') {
+                    self.position += 1;
+                } else if !self.peek_is(')') {
+                    return Err(match self.tokens.get(self.position) {
+                        Some(token) => format!("expected `,` or `)` but found {token}"),
+                        None => "expected `)` but the noise graph ended".to_owned(),
+                    });
+                }
+            }
+            self.position += 1;
+        }
+
+        if let Some(index) = NOISE_FN_IDENTIFIERS
+            .iter()
+            .position(|id| same_name(id, &name))
+        {
+            return generator(&name, index, positional, named);
+        }
+        let Some(op) = Op::ALL.into_iter().find(|op| same_name(op.name(), &name)) else {
+            return Err(format!("unknown noise `{name}`"));
+        };
+
+        op_node(op, positional, named)
+    }
+
+    fn arg(&mut self) -> Result<Arg, String> {
+        if let Some(Token::Number(value)) = self.tokens.get(self.position) {
+            let value = *value;
+            self.position += 1;
+            return Ok(Arg::Number(value));
+        }
+
+        Ok(Arg::Node(self.node()?))
+    }
+}
+
+fn generator(
+    name: &str,
+    index: usize,
+    positional: Vec<Arg>,
+    named: Vec<(String, Arg)>,
+) -> Result<Node, String> {
+    let mut fractal = FractalParams::default_for_index(index);
+    let mut seed = 0;
+    let param_names: Vec<&str> = match fractal {
+        Some(_) => FractalParams::FIELD_NAMES.iter().copied().chain(["seed"]).collect(),
+        None => vec!["seed"],
+    };
+
+    let positional = positional.into_iter().enumerate().map(|(i, arg)| {
+        let param_name = param_names.get(i).copied().unwrap_or_default();
+        (param_name.to_owned(), arg)
+    });
+    for (arg_name, arg) in positional.chain(named) {
+        let Arg::Number(value) = arg else {
+            return Err(format!("`{name}` doesn't take any sources"));
+        };
+        match (arg_name.as_str(), &mut fractal) {
+            ("seed", _) if value >= 0.0 => seed = value as u32,
+            ("octaves", Some(fractal)) if value >= 1.0 => fractal.octaves = value as usize,
+            ("frequency", Some(fractal)) => fractal.frequency = value,
+            ("lacunarity", Some(fractal)) => fractal.lacunarity = value,
+            ("persistence", Some(fractal)) => fractal.persistence = value,
+            ("", _) => return Err(format!("`{name}` was given too many settings")),
+            (arg_name, _) if param_names.contains(&arg_name) => {
+                return Err(format!("`{name}` can't have a `{arg_name}` of {value}"));
+            }
+            _ => return Err(format!("`{name}` has no `{arg_name}` setting")),
+        }
+    }
+
+    Ok(Node::Generator {
+        index,
+        seed,
+        fractal,
+    })
+}
+
+fn op_node(op: Op, positional: Vec<Arg>, named: Vec<(String, Arg)>) -> Result<Node, String> {
+    let name = op.name();
+    let mut sources: Vec<Option<Node>> = op.sources().iter().map(|_| None).collect();
+    let mut params: Vec<Option<f64>> = op.params().iter().map(|_| None).collect();
+    let (mut next_source, mut next_param) = (0, 0);
+
+    for arg in positional {
+        match arg {
+            Arg::Node(node) if next_source < sources.len() => {
+                sources[next_source] = Some(node);
+                next_source += 1;
+            }
+            Arg::Number(value) if next_param < params.len() => {
+                params[next_param] = Some(value);
+                next_param += 1;
+            }
+            Arg::Node(_) => return Err(format!("`{name}` was given too many sources")),
+            Arg::Number(_) => return Err(format!("`{name}` was given too many settings")),
+        }
+    }
+
+    for (arg_name, arg) in named {
+        let source = op.sources().iter().position(|&source| source == arg_name);
+        let param = op.params().iter().position(|param| param.name == arg_name);
+        let slot_filled = match (arg, source, param) {
+            (Arg::Node(node), Some(i), _) => sources[i].replace(node).is_some(),
+            (Arg::Number(value), _, Some(i)) => params[i].replace(value).is_some(),
+            (Arg::Node(_), None, _) => {
+                return Err(format!("`{name}` has no `{arg_name}` source"));
+            }
+            (Arg::Number(_), _, None) => {
+                return Err(format!("`{name}` has no `{arg_name}` setting"));
+            }
+        };
+        if slot_filled {
+            return Err(format!("`{name}` was given `{arg_name}` twice"));
+        }
+    }
+
+    let sources = sources
+        .into_iter()
+        .zip(op.sources())
+        .map(|(source, source_name)| {
+            source.ok_or_else(|| format!("`{name}` needs a `{source_name}` source"))
+        })
+        .collect::<Result<_, _>>()?;
+    let params = params
+        .into_iter()
+        .zip(op.params())
+        .map(|(value, param)| value.unwrap_or(param.default).clamp(param.min, param.max))
+        .collect();
+
+    Ok(Node::Op {
+        op,
+        sources,
+        params,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn graph(source: &str) -> NoiseGraph {
+        source.parse().unwrap()
+    }
+
+    fn error(source: &str) -> String {
+        source.parse::<NoiseGraph>().unwrap_err()
+    }
+
+    #[test]
+    fn display_parses_back() {
+        let sources = [
+            "Perlin",
+            "perlin(seed = 3)",
+            "Fbm(octaves = 3, frequency = 0.5)",
+            "open_simplex",
+            "Blend(Perlin, Worley, control = Fbm(octaves = 3))",
+            "Select(Value, Billow(lacunarity = 1.5), Fbm, lower = -0.25, falloff = 0.1)",
+            "Turbulence(Add(Perlin, Constant(0.5)), power = 2, roughness = 5, seed = 7)",
+            "ScaleBias(Abs(Negate(HybridMulti(persistence = 0.3))), scale = 0.5, bias = 0.1)",
+            "Clamp(Exponent(BasicMulti, exponent = 2), lower = -0.5)",
+            "Min(Max(Perlin, Value), Power(Worley, DomainWarp))",
+        ];
+
+        for source in sources {
+            let original = graph(source);
+            let written = original.to_string();
+            let reparsed = graph(&written);
+            assert_eq!(reparsed, original, "{source} was written as {written}");
+            assert_eq!(reparsed.to_string(), written);
+
+            let [original, reparsed] = [original, reparsed].map(|graph| graph.build(5, None));
+            for p in [[0.1, 0.2, 0.3], [12.5, -3.0, 7.0]] {
+                assert_eq!(original.get(p), reparsed.get(p));
+            }
+        }
+    }
+
+    #[test]
+    fn display_writes_only_what_differs_from_the_defaults() {
+        assert_eq!(graph("fbm(octaves = 6, seed = 0)").to_string(), "Fbm");
+        assert_eq!(
+            graph("blend(a = perlin, b = worley, control = open_simplex)").to_string(),
+            "Blend(Perlin, Worley, OpenSimplex)"
+        );
+        assert_eq!(
+            graph("select(perlin, value, worley, lower = 0, upper = 0.5)").to_string(),
+            "Select(Perlin, Value, Worley, upper = 0.5)"
+        );
+    }
+
+    #[test]
+    fn args_by_position_and_by_name() {
+        let pairs = [
+            (
+                "Select(Perlin, Worley, Fbm, 0.2, 0.8, 0.1)",
+                "Select(control = Fbm, lower = 0.2, b = Worley, falloff = 0.1, a = Perlin, \
+                 upper = 0.8)",
+            ),
+            (
+                "Blend(Perlin, Worley, control = Value)",
+                "Blend(Perlin, control = Value, b = Worley)",
+            ),
+            ("Fbm(3, 2)", "Fbm(frequency = 2, octaves = 3)"),
+            (
+                "Billow(4, 1, 2.5, 0.4, 9)",
+                "Billow(seed = 9, persistence = 0.4, lacunarity = 2.5, octaves = 4)",
+            ),
+            ("Perlin(5)", "Perlin(seed = 5)"),
+            ("Constant(0.25)", "Constant(value = 0.25)"),
+        ];
+
+        for (by_position, by_name) in pairs {
+            assert_eq!(graph(by_position), graph(by_name), "{by_position} and {by_name}");
+        }
+    }
+
+    #[test]
+    fn names_ignore_case_and_underscores() {
+        assert_eq!(graph("OpenSimplex"), graph("open_simplex"));
+        assert_eq!(graph("scale_bias(basic_multi)"), graph("ScaleBias(BasicMulti)"));
+    }
+
+    #[test]
+    fn duplicate_and_missing_sources() {
+        assert_eq!(error("Add(Perlin, Worley, a = Value)"), "`Add` was given `a` twice");
+        assert_eq!(error("Clamp(Perlin, -0.5, lower = 0)"), "`Clamp` was given `lower` twice");
+        assert_eq!(error("Blend(Perlin, Worley)"), "`Blend` needs a `control` source");
+        assert_eq!(error("Add(b = Perlin)"), "`Add` needs a `a` source");
+        assert_eq!(error("Abs"), "`Abs` needs a `source` source");
+        assert_eq!(error("Abs(Perlin, Value)"), "`Abs` was given too many sources");
+        assert_eq!(error("Exponent(Perlin, 1, 2)"), "`Exponent` was given too many settings");
+    }
+
+    #[test]
+    fn errors() {
+        assert_eq!(error(""), "noise graph ended early");
+        assert_eq!(error("Simplex"), "unknown noise `Simplex`");
+        assert_eq!(error("Abs(source = 1)"), "`Abs` has no `source` setting");
+        assert_eq!(error("Abs(Perlin, input = Value)"), "`Abs` has no `input` source");
+        assert_eq!(error("Perlin(Value)"), "`Perlin` doesn't take any sources");
+        assert_eq!(error("Perlin(octaves = 3)"), "`Perlin` has no `octaves` setting");
+        assert_eq!(error("Perlin(1, 2)"), "`Perlin` was given too many settings");
+        assert_eq!(error("Fbm(octaves = 0)"), "`Fbm` can't have a `octaves` of 0");
+        assert_eq!(error("Perlin(seed = -1)"), "`Perlin` can't have a `seed` of -1");
+        assert_eq!(error("Abs(Perlin"), "expected `)` but the noise graph ended");
+        assert_eq!(error("Add(Perlin Worley)"), "expected `,` or `)` but found `Worley`");
+        assert_eq!(
+            error("Abs(Perlin) Value"),
+            "unexpected `Value` after the end of the noise graph"
+        );
+        assert_eq!(error("Abs(=)"), "expected a noise but found `=`");
+        assert_eq!(error("Constant(1.2.3)"), "`1.2.3` isn't a number");
+        assert_eq!(error("Perlin * Worley"), "unexpected character `*` in noise graph");
+    }
+
+    #[test]
+    fn params_are_clamped() {
+        assert_eq!(
+            graph("Turbulence(Perlin, frequency = -2, roughness = 100, seed = -3)"),
+            graph("Turbulence(Perlin, frequency = 0, roughness = 32, seed = 0)")
+        );
+        assert_eq!(
+            graph("Turbulence(Perlin, roughness = 0)"),
+            graph("Turbulence(Perlin, roughness = 1)")
+        );
+        assert_eq!(
+            graph("Select(Perlin, Worley, Value, falloff = -1)").to_string(),
+            "Select(Perlin, Worley, Value)"
+        );
+
+        // Nudging stops at the same limits
+        let mut graph = graph("Turbulence(Perlin, roughness = 1)");
+        assert_eq!(graph.param_name(2), "1 Turbulence roughness");
+        graph.nudge_param(2, false);
+        assert_eq!(graph.param_value(2), "1");
+        for _ in 0..40 {
+            graph.nudge_param(2, true);
+        }
+        assert_eq!(graph.param_value(2), "32");
+    }
+
+    #[test]
+    fn deep_nesting_is_an_error() {
+        let depth = 5_000;
+        let nested = format!("{}Perlin{}", "Abs(".repeat(depth), ")".repeat(depth));
+        assert_eq!(error(&nested), format!("noise graph is nested more than {MAX_NESTING} deep"));
+
+        let nested = format!("{}Perlin{}", "Abs(".repeat(150), ")".repeat(150));
+        assert!(nested.parse::<NoiseGraph>().is_ok());
+    }
+}
@@ -0,0 +1,31 @@
+use noise::NoiseFn;
+use std::f64::consts::TAU;
+
+/// Samples 4D noise around a circle in its last two dimensions, so that time loops.
+///
+/// The time coordinate is a phase from 0 to 1 around the loop. The circle is `loop_length` around,
+/// which is how far time would have moved through 3D noise over the same loop.
+pub struct LoopingNoise<T> {
+    noise: T,
+    radius: f64,
+}
+
+impl<T> LoopingNoise<T> {
+    pub fn new(noise: T, loop_length: f64) -> Self {
+        Self {
+            noise,
+            radius: loop_length / TAU,
+        }
+    }
+}
+
+impl<T> NoiseFn<f64, 3> for LoopingNoise<T>
+where
+    T: NoiseFn<f64, 4>,
+{
+    fn get(&self, [x, y, phase]: [f64; 3]) -> f64 {
+        let (sin, cos) = (phase * TAU).sin_cos();
+
+        self.noise.get([x, y, self.radius * cos, self.radius * sin])
+    }
+}
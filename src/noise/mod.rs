@@ -1,50 +1,202 @@
 mod domain_warping_noise;
+mod graph;
+mod looping_noise;
+
+pub use domain_warping_noise::{DomainWarpingNoiseParams, WarpPreset};
+pub use graph::NoiseGraph;
+pub use looping_noise::LoopingNoise;
 
 use domain_warping_noise::DomainWarpingNoise;
-use noise::{NoiseFn, Simplex};
+use noise::{MultiFractal, NoiseFn, Simplex};
+use serde::{Deserialize, Serialize};
 
-use crate::noise::domain_warping_noise::DomainWarpingNoiseParams;
+/// How many noise generators `new_noise_fn_by_index` knows about.
+pub const NOISE_FN_COUNT: usize = 9;
 
-pub fn new_noise_fn_by_index(index: usize) -> Box<dyn NoiseFn<f64, 3>> {
-    match index {
-        0 => {
-            log::info!("now using Domain Warping Noise generator");
-            let dwn_params = DomainWarpingNoiseParams::random();
-            Box::new(DomainWarpingNoise::<Simplex>::new(dwn_params))
+pub const NOISE_FN_NAMES: [&str; NOISE_FN_COUNT] = [
+    "Domain Warping Noise",
+    "Billowy Noise",
+    "Heterogenous Multifractal Noise",
+    "Fractal Brownian Motion Noise",
+    "Hybrid Multifractal Noise",
+    "Open Simplex Noise",
+    "Perlin Noise",
+    "Value Noise",
+    "Worley Noise",
+];
+
+/// The names that formulas and noise graphs use for each generator.
+pub const NOISE_FN_IDENTIFIERS: [&str; NOISE_FN_COUNT] = [
+    "domain_warp",
+    "billow",
+    "basic_multi",
+    "fbm",
+    "hybrid_multi",
+    "open_simplex",
+    "perlin",
+    "value",
+    "worley",
+];
+
+/// Settings shared by the fractal noise generators.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct FractalParams {
+    pub octaves: usize,
+    pub frequency: f64,
+    pub lacunarity: f64,
+    pub persistence: f64,
+}
+
+impl FractalParams {
+    pub const FIELD_NAMES: [&str; 4] = ["octaves", "frequency", "lacunarity", "persistence"];
+    const MAX_OCTAVES: usize = 32;
+
+    /// The noise crate's defaults for the generator at `index`, or `None` if it isn't fractal.
+    pub fn default_for_index(index: usize) -> Option<Self> {
+        let (frequency, persistence) = match index {
+            // Domain warping noise warps Fbm unless it's given another base
+            0 | 1 | 3 => (
+                noise::Fbm::<Simplex>::DEFAULT_FREQUENCY,
+                noise::Fbm::<Simplex>::DEFAULT_PERSISTENCE,
+            ),
+            2 => (
+                noise::BasicMulti::<Simplex>::DEFAULT_FREQUENCY,
+                noise::BasicMulti::<Simplex>::DEFAULT_PERSISTENCE,
+            ),
+            4 => (
+                noise::HybridMulti::<Simplex>::DEFAULT_FREQUENCY,
+                noise::HybridMulti::<Simplex>::DEFAULT_PERSISTENCE,
+            ),
+            _ => return None,
+        };
+
+        Some(Self {
+            octaves: noise::Fbm::<Simplex>::DEFAULT_OCTAVE_COUNT,
+            frequency,
+            lacunarity: noise::Fbm::<Simplex>::DEFAULT_LACUNARITY,
+            persistence,
+        })
+    }
+
+    /// The defaults for the generator at `index` set up with `warp`. Domain warping noise passes
+    /// its fractal settings on to the generator it warps, so it takes that generator's defaults,
+    /// and has none if that generator isn't fractal.
+    pub fn default_for_noise(index: usize, warp: Option<&DomainWarpingNoiseParams>) -> Option<Self> {
+        match (index, warp) {
+            (0, Some(warp)) => Self::default_for_index(warp.base()),
+            _ => Self::default_for_index(index),
         }
+    }
+
+    /// Format the field at `field` (an index into `FIELD_NAMES`) for display.
+    pub fn field_value(&self, field: usize) -> String {
+        match field {
+            0 => self.octaves.to_string(),
+            1 => format!("{:.3}", self.frequency),
+            2 => format!("{:.3}", self.lacunarity),
+            3 => format!("{:.3}", self.persistence),
+            _ => unreachable!(),
+        }
+    }
+
+    /// Nudge the field at `field` (an index into `FIELD_NAMES`) up or down by one step.
+    pub fn nudge(&mut self, field: usize, increase: bool) {
+        let sign = if increase { 1.0 } else { -1.0 };
+
+        match field {
+            0 if increase => self.octaves = (self.octaves + 1).min(Self::MAX_OCTAVES),
+            0 => self.octaves = self.octaves.saturating_sub(1).max(1),
+            1 => self.frequency *= if increase { 1.1 } else { 1.0 / 1.1 },
+            2 => self.lacunarity = (self.lacunarity + sign * 0.05).max(0.05),
+            3 => self.persistence = (self.persistence + sign * 0.05).clamp(0.0, 1.0),
+            _ => unreachable!(),
+        }
+    }
+
+    pub fn apply<T: MultiFractal>(&self, noise: T) -> T {
+        noise
+            .set_octaves(self.octaves)
+            .set_frequency(self.frequency)
+            .set_lacunarity(self.lacunarity)
+            .set_persistence(self.persistence)
+    }
+}
+
+/// Create the noise generator at `index`. Fractal generators use `fractal` if it's passed in,
+/// otherwise they use the noise crate's defaults. Domain warping noise likewise uses `warp`, or
+/// random parameters from `seed`. With a `loop_length`, time goes around a `LoopingNoise` of that
+/// length instead of on forever.
+pub fn new_noise_fn_by_index(
+    index: usize,
+    seed: u32,
+    fractal: Option<&FractalParams>,
+    warp: Option<&DomainWarpingNoiseParams>,
+    loop_length: Option<f64>,
+) -> Box<dyn NoiseFn<f64, 3>> {
+    log::info!("now using {} generator", NOISE_FN_NAMES[index]);
+    noise_fn_by_index(index, seed, fractal, warp, loop_length)
+}
+
+/// The same as `new_noise_fn_by_index`, for generators that are only used behind the scenes.
+pub fn noise_fn_by_index(
+    index: usize,
+    seed: u32,
+    fractal: Option<&FractalParams>,
+    warp: Option<&DomainWarpingNoiseParams>,
+    loop_length: Option<f64>,
+) -> Box<dyn NoiseFn<f64, 3>> {
+    let generator = generator_by_index(index, seed, fractal, warp);
+
+    match loop_length {
+        Some(loop_length) => Box::new(LoopingNoise::new(generator, loop_length)),
+        None => generator,
+    }
+}
+
+/// Every generator can be sampled in 3D, or in 4D for looping animations.
+pub trait Generator: NoiseFn<f64, 3> + NoiseFn<f64, 4> {}
+
+impl<T: NoiseFn<f64, 3> + NoiseFn<f64, 4>> Generator for T {}
+
+fn generator_by_index(
+    index: usize,
+    seed: u32,
+    fractal: Option<&FractalParams>,
+    warp: Option<&DomainWarpingNoiseParams>,
+) -> Box<dyn Generator> {
+    // Domain warping noise leaves any defaults to the generator it warps
+    if index == 0 {
+        let dwn_params = warp
+            .cloned()
+            .unwrap_or_else(|| DomainWarpingNoiseParams::random(seed));
+        return Box::new(DomainWarpingNoise::new(dwn_params, seed, fractal));
+    }
+
+    let fractal = fractal
+        .copied()
+        .or_else(|| FractalParams::default_for_index(index));
+
+    match index {
         1 => {
-            log::info!("now using Billowy Noise generator");
-            // TODO allow setting frequency, lacunarity, octaves, persistence
-            Box::new(noise::Billow::<Simplex>::new(0))
+            let fractal = fractal.expect("billowy noise is fractal");
+            Box::new(fractal.apply(noise::Billow::<Simplex>::new(seed)))
         }
         2 => {
-            log::info!("now using Heterogenous Multifractal Noise generator");
-            Box::new(noise::BasicMulti::<Simplex>::new(0))
+            let fractal = fractal.expect("heterogenous multifractal noise is fractal");
+            Box::new(fractal.apply(noise::BasicMulti::<Simplex>::new(seed)))
         }
         3 => {
-            log::info!("now using Fractal Brownian Motion Noise generator");
-            Box::new(noise::Fbm::<Simplex>::new(0))
+            let fractal = fractal.expect("fractal brownian motion noise is fractal");
+            Box::new(fractal.apply(noise::Fbm::<Simplex>::new(seed)))
         }
         4 => {
-            log::info!("now using Hybrid Multifractal Noise generator");
-            Box::new(noise::HybridMulti::<Simplex>::new(0))
-        }
-        5 => {
-            log::info!("now using Open Simplex Noise generator");
-            Box::new(noise::OpenSimplex::new(0))
-        }
-        6 => {
-            log::info!("now using Perlin Noise generator");
-            Box::new(noise::Perlin::new(0))
-        }
-        7 => {
-            log::info!("now using Value Noise generator");
-            Box::new(noise::Value::new(0))
-        }
-        8 => {
-            log::info!("now using Worley Noise generator");
-            Box::new(noise::Worley::new(0))
+            let fractal = fractal.expect("hybrid multifractal noise is fractal");
+            Box::new(fractal.apply(noise::HybridMulti::<Simplex>::new(seed)))
         }
+        5 => Box::new(noise::OpenSimplex::new(seed)),
+        6 => Box::new(noise::Perlin::new(seed)),
+        7 => Box::new(noise::Value::new(seed)),
+        8 => Box::new(noise::Worley::new(seed)),
         _ => unreachable!(),
     }
 }
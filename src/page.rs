@@ -0,0 +1,411 @@
+use crate::{
+    consts::{SCREEN_H, SCREEN_W},
+    settings::{Choice, PX_PER_MM},
+};
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+use std::{fmt, str::FromStr};
+use svg::node::{element::Group, Node};
+
+/// The size of the sheet an export is laid out on. `Screen` keeps the canvas's own pixel size.
+///
+/// Written as a name like `a4` or `letter`, or as `WIDTHxHEIGHT` in millimetres for any other
+/// size, e.g. `300x200`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub enum Paper {
+    #[default]
+    Screen,
+    A5,
+    A4,
+    A3,
+    A2,
+    Letter,
+    Legal,
+    Tabloid,
+    /// Width and height in millimetres
+    Custom(f64, f64),
+}
+
+impl Paper {
+    /// Width and height in millimetres, portrait side up for named sizes and exactly as given for
+    /// custom ones, or `None` for `Paper::Screen`.
+    pub fn size(self) -> Option<[f64; 2]> {
+        match self {
+            Paper::Screen => None,
+            Paper::A5 => Some([148.0, 210.0]),
+            Paper::A4 => Some([210.0, 297.0]),
+            Paper::A3 => Some([297.0, 420.0]),
+            Paper::A2 => Some([420.0, 594.0]),
+            Paper::Letter => Some([215.9, 279.4]),
+            Paper::Legal => Some([215.9, 355.6]),
+            Paper::Tabloid => Some([279.4, 431.8]),
+            Paper::Custom(width, height) => Some([width, height]),
+        }
+    }
+}
+
+impl Choice for Paper {
+    // Custom sizes can only come from a preset or the command line
+    const ALL: &'static [Self] = &[
+        Paper::Screen,
+        Paper::A5,
+        Paper::A4,
+        Paper::A3,
+        Paper::A2,
+        Paper::Letter,
+        Paper::Legal,
+        Paper::Tabloid,
+    ];
+}
+
+impl FromStr for Paper {
+    type Err = String;
+
+    fn from_str(source: &str) -> Result<Self, Self::Err> {
+        let source = source.trim().to_lowercase();
+
+        if let Some(paper) = Self::ALL.iter().find(|paper| paper.to_string() == source) {
+            return Ok(*paper);
+        }
+
+        let size = source.split_once('x').and_then(|(width, height)| {
+            Some((width.trim().parse::<f64>().ok()?, height.trim().parse::<f64>().ok()?))
+        });
+
+        match size {
+            Some((width, height)) if width > 0.0 && height > 0.0 => {
+                Ok(Paper::Custom(width, height))
+            }
+            Some(_) => Err(format!("paper size {source} must be bigger than 0x0 mm")),
+            None => Err(format!(
+                "unknown paper {source}, expected one of {} or a size like 300x200 in mm",
+                Self::ALL.iter().map(Paper::to_string).collect::<Vec<_>>().join(", ")
+            )),
+        }
+    }
+}
+
+impl TryFrom<String> for Paper {
+    type Error = String;
+
+    fn try_from(source: String) -> Result<Self, Self::Error> {
+        source.parse()
+    }
+}
+
+impl From<Paper> for String {
+    fn from(paper: Paper) -> Self {
+        paper.to_string()
+    }
+}
+
+impl fmt::Display for Paper {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Paper::Screen => write!(f, "screen"),
+            Paper::A5 => write!(f, "a5"),
+            Paper::A4 => write!(f, "a4"),
+            Paper::A3 => write!(f, "a3"),
+            Paper::A2 => write!(f, "a2"),
+            Paper::Letter => write!(f, "letter"),
+            Paper::Legal => write!(f, "legal"),
+            Paper::Tabloid => write!(f, "tabloid"),
+            Paper::Custom(width, height) => write!(f, "{width}x{height}"),
+        }
+    }
+}
+
+/// Which way up the paper is.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, ValueEnum)]
+#[serde(rename_all = "snake_case")]
+pub enum Orientation {
+    Portrait,
+    /// The same way up as the screen
+    #[default]
+    Landscape,
+}
+
+impl Choice for Orientation {
+    const ALL: &'static [Self] = &[Orientation::Portrait, Orientation::Landscape];
+}
+
+impl fmt::Display for Orientation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Orientation::Portrait => write!(f, "portrait"),
+            Orientation::Landscape => write!(f, "landscape"),
+        }
+    }
+}
+
+/// How exports are laid out on paper, so that they plot at the right size as they are.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PageSetup {
+    pub paper: Paper,
+    pub orientation: Orientation,
+    /// The blank space kept around every edge of the paper, in millimetres
+    pub margin: f64,
+}
+
+/// Where the canvas ends up on the page: canvas pixels are multiplied by `scale` and then moved
+/// by `offset` to get millimetres from the top left corner of the paper.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Placement {
+    pub scale: f64,
+    pub offset: [f64; 2],
+}
+
+impl Placement {
+    pub fn apply(&self, [x, y]: [f64; 2]) -> [f64; 2] {
+        [x * self.scale + self.offset[0], y * self.scale + self.offset[1]]
+    }
+}
+
+impl PageSetup {
+    pub const FIELD_NAMES: [&str; 3] = ["paper", "orientation", "margin"];
+
+    /// Width and height of the paper in millimetres the way it's turned, or `None` for
+    /// `Paper::Screen`. Custom sizes already say which way up they are, so they aren't turned.
+    pub fn size(&self) -> Option<[f64; 2]> {
+        let [width, height] = self.paper.size()?;
+
+        match (self.paper, self.orientation) {
+            (Paper::Custom(..), _) | (_, Orientation::Portrait) => Some([width, height]),
+            (_, Orientation::Landscape) => Some([height, width]),
+        }
+    }
+
+    /// Check that the margins leave some of the paper to draw on.
+    pub fn validate(&self) -> Result<(), String> {
+        if self.margin < 0.0 {
+            return Err("page margin can't be negative".to_owned());
+        }
+
+        match self.placement() {
+            Some(placement) if placement.scale <= 0.0 => Err(format!(
+                "a {} mm margin leaves no room on {} paper",
+                self.margin, self.paper
+            )),
+            _ => Ok(()),
+        }
+    }
+
+    /// The biggest scale that fits the whole canvas inside the margins, centred on the paper,
+    /// or `None` for `Paper::Screen`.
+    pub fn placement(&self) -> Option<Placement> {
+        let [width, height] = self.size()?;
+        let printable_width = width - self.margin * 2.0;
+        let printable_height = height - self.margin * 2.0;
+        let scale = (printable_width / SCREEN_W as f64).min(printable_height / SCREEN_H as f64);
+
+        Some(Placement {
+            scale,
+            offset: [
+                (width - SCREEN_W as f64 * scale) / 2.0,
+                (height - SCREEN_H as f64 * scale) / 2.0,
+            ],
+        })
+    }
+
+    /// Where the canvas ends up on the paper and how big the paper is, in millimetres. Screen sized
+    /// pages are as big as SVG viewers show the canvas, without margins.
+    pub fn layout(&self) -> (Placement, [f64; 2]) {
+        // Rounded to the micrometre, so that the size doesn't come out as 507.99999999999994mm
+        let mm = |px: usize| (px as f64 / PX_PER_MM * 1000.0).round() / 1000.0;
+
+        match (self.placement(), self.size()) {
+            (Some(placement), Some(size)) => (placement, size),
+            _ => (
+                Placement {
+                    scale: 1.0 / PX_PER_MM,
+                    offset: [0.0, 0.0],
+                },
+                [mm(SCREEN_W), mm(SCREEN_H)],
+            ),
+        }
+    }
+
+    /// How wide a canvas pixel comes out on the paper, in millimetres.
+    pub fn mm_per_px(&self) -> f64 {
+        self.layout().0.scale
+    }
+
+    /// Lay an SVG built on the canvas out on the paper. The document gets its real size in
+    /// millimetres, and its user units become millimetres too.
+    pub fn fit(&self, mut document: svg::Document) -> svg::Document {
+        let (placement, [width, height]) = self.layout();
+
+        let mut group = Group::new().set(
+            "transform",
+            format!(
+                "translate({} {}) scale({})",
+                placement.offset[0], placement.offset[1], placement.scale
+            ),
+        );
+        let children = document.get_children_mut().map(std::mem::take).unwrap_or_default();
+        for mut child in children {
+            // Pen widths are meant as millimetres on paper, not to be scaled along with the canvas
+            unscale_stroke_widths(child.as_mut(), placement.scale);
+            group = group.add(child);
+        }
+
+        svg::Document::new()
+            .set("width", format!("{width}mm"))
+            .set("height", format!("{height}mm"))
+            .set("viewBox", (0, 0, width, height))
+            .add(group)
+    }
+
+    /// Format the field at `field` (an index into `FIELD_NAMES`) for display.
+    pub fn field_value(&self, field: usize) -> String {
+        match field {
+            0 => match self.size() {
+                Some([width, height]) => format!("{} ({width} x {height} mm)", self.paper),
+                None => "screen size".to_owned(),
+            },
+            1 => self.orientation.to_string(),
+            2 => format!("{} mm", self.margin),
+            _ => unreachable!(),
+        }
+    }
+
+    /// Nudge the field at `field` (an index into `FIELD_NAMES`) up or down by one step.
+    pub fn nudge(&mut self, field: usize, increase: bool) {
+        match field {
+            0 => {
+                self.paper = match self.paper {
+                    Paper::Custom(..) => Paper::ALL[0],
+                    paper => paper.cycled(increase),
+                }
+            }
+            1 => self.orientation = self.orientation.cycled(increase),
+            2 => {
+                let mut page = *self;
+                page.margin = (self.margin + if increase { 1.0 } else { -1.0 }).max(0.0);
+                if page.validate().is_ok() {
+                    *self = page;
+                }
+            }
+            _ => unreachable!(),
+        }
+    }
+}
+
+impl Default for PageSetup {
+    fn default() -> Self {
+        Self {
+            paper: Paper::default(),
+            orientation: Orientation::default(),
+            margin: 10.0,
+        }
+    }
+}
+
+/// Turn every `stroke-width` in millimetres under `node` into user units that come out at that
+/// many millimetres once they've been scaled by `scale`.
+fn unscale_stroke_widths(node: &mut dyn Node, scale: f64) {
+    if let Some(attributes) = node.get_attributes_mut()
+        && let Some(width) = attributes.get("stroke-width")
+        && let Some(mm) = width.strip_suffix("mm").and_then(|mm| mm.parse::<f64>().ok())
+    {
+        attributes.insert("stroke-width".to_owned(), (mm / scale).into());
+    }
+
+    if let Some(children) = node.get_children_mut() {
+        for child in children {
+            unscale_stroke_widths(child.as_mut(), scale);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use svg::node::element::Path;
+
+    fn page(paper: &str, orientation: Orientation) -> PageSetup {
+        PageSetup {
+            paper: paper.parse().unwrap(),
+            orientation,
+            ..PageSetup::default()
+        }
+    }
+
+    fn assert_near(actual: f64, expected: f64) {
+        assert!((actual - expected).abs() < 1e-9, "{actual} isn't {expected}");
+    }
+
+    /// The transform and the first stroke width `fit` gave the document.
+    fn transform_and_stroke_width(document: &svg::Document) -> (String, f64) {
+        let group = &document.get_children().unwrap()[0];
+        let path = &group.get_children().unwrap()[0];
+        let transform = group.get_attributes().unwrap()["transform"].to_string();
+        let width = path.get_attributes().unwrap()["stroke-width"].parse().unwrap();
+
+        (transform, width)
+    }
+
+    #[test]
+    fn named_papers_are_turned() {
+        assert_eq!(page("a4", Orientation::Landscape).size(), Some([297.0, 210.0]));
+        assert_eq!(page("a4", Orientation::Portrait).size(), Some([210.0, 297.0]));
+        assert_eq!(page("screen", Orientation::Portrait).size(), None);
+    }
+
+    #[test]
+    fn custom_papers_are_kept_as_given() {
+        for orientation in Orientation::ALL {
+            assert_eq!(page("200x300", *orientation).size(), Some([200.0, 300.0]));
+            assert_eq!(page("300x200", *orientation).size(), Some([300.0, 200.0]));
+        }
+    }
+
+    #[test]
+    fn placement_fills_the_margins_and_centres() {
+        let page = page("a4", Orientation::Landscape);
+        let placement = page.placement().unwrap();
+
+        // The canvas is wider than A4 landscape, so its width fills the space between the margins
+        assert_near(placement.scale, 277.0 / SCREEN_W as f64);
+        assert_eq!(placement.apply([0.0, 0.0])[0], 10.0);
+        assert_near(placement.apply([SCREEN_W as f64, 0.0])[0], 287.0);
+
+        let [_, top] = placement.apply([0.0, 0.0]);
+        let [_, bottom] = placement.apply([0.0, SCREEN_H as f64]);
+        assert!(top > 10.0);
+        assert_near(top, 210.0 - bottom);
+    }
+
+    #[test]
+    fn screen_pages_are_the_canvas_in_mm() {
+        let page = page("screen", Orientation::Landscape);
+        let (placement, size) = page.layout();
+
+        assert_eq!(page.placement(), None);
+        assert_eq!(placement.offset, [0.0, 0.0]);
+        assert_near(placement.scale, 1.0 / PX_PER_MM);
+        assert_near(size[0], 508.0);
+        assert_near(size[1], 285.75);
+    }
+
+    #[test]
+    fn fit_scales_the_canvas_but_not_the_pens() {
+        for (paper, width) in [("a4", 297.0), ("screen", 508.0)] {
+            let page = page(paper, Orientation::Landscape);
+            let placement = page.layout().0;
+            let document = svg::Document::new()
+                .set("viewBox", (0, 0, SCREEN_W, SCREEN_H))
+                .add(Path::new().set("stroke-width", "0.3mm"));
+
+            let document = page.fit(document);
+            let (transform, stroke_width) = transform_and_stroke_width(&document);
+
+            let attributes = document.get_attributes().unwrap();
+            assert_near(attributes["width"].strip_suffix("mm").unwrap().parse().unwrap(), width);
+            assert!(transform.ends_with(&format!("scale({})", placement.scale)));
+            assert_near(stroke_width * placement.scale, 0.3);
+        }
+    }
+}
@@ -0,0 +1,361 @@
+use crate::{
+    consts::{
+        GRID_SIZE_Y, PARTICLE_HISTORY_PATHS, PARTICLE_HISTORY_POINTS, PARTICLE_SPEED, SCREEN_H,
+        SCREEN_W, VECTOR_WIDTH,
+    },
+    settings::{ParticleSeeding, Setting},
+    spatial_grid::SpatialGrid,
+    streamlines::{build_svg_document_from_polylines, is_on_screen, wrap_polyline, Integrator},
+    visualizer::{Visualizer, VisualizerParams},
+};
+use macroquad::prelude::*;
+use ::rand::{prelude::StdRng, Rng, SeedableRng};
+use std::{collections::VecDeque, f64::consts::TAU};
+
+type Point2<T> = [T; 2];
+
+struct Particle {
+    /// Where the particle first appeared. Grid and Poisson disk particles respawn here.
+    spawn: Point2<f64>,
+    /// Where the particle has been, oldest first. The last point is where it is now.
+    trail: VecDeque<Point2<f64>>,
+    /// Points that have faded off the start of `trail`, which are still exported. They're handed
+    /// over to `History` every `FADED_PIECE_LENGTH` points.
+    faded: Vec<Point2<f64>>,
+    alive: bool,
+}
+
+impl Particle {
+    fn new(spawn: Point2<f64>) -> Self {
+        Self {
+            spawn,
+            trail: VecDeque::from([spawn]),
+            faded: Vec::new(),
+            alive: true,
+        }
+    }
+
+    /// Where the particle has been since it last spawned or handed its faded points over.
+    fn path(&self) -> Vec<Point2<f64>> {
+        self.faded.iter().chain(self.trail.iter()).copied().collect()
+    }
+}
+
+/// How many faded points a particle holds on to before handing them over to `History`, so that
+/// particles that never respawn have their history trimmed too.
+const FADED_PIECE_LENGTH: usize = 256;
+
+/// The faded and finished paths of the particles, oldest first, kept for exporting within a
+/// budget of points and paths.
+struct History {
+    paths: VecDeque<Vec<Point2<f64>>>,
+    points: usize,
+    max_points: usize,
+    max_paths: usize,
+}
+
+impl History {
+    fn new(max_points: usize, max_paths: usize) -> Self {
+        Self {
+            paths: VecDeque::new(),
+            points: 0,
+            max_points,
+            max_paths,
+        }
+    }
+
+    /// Keep `path`, dropping the oldest paths if that goes over budget.
+    fn push(&mut self, path: Vec<Point2<f64>>) {
+        if path.len() < 2 {
+            return;
+        }
+
+        self.points += path.len();
+        self.paths.push_back(path);
+
+        while (self.points > self.max_points || self.paths.len() > self.max_paths)
+            && let Some(oldest) = self.paths.pop_front()
+        {
+            self.points -= oldest.len();
+        }
+    }
+
+    fn clear(&mut self) {
+        self.paths.clear();
+        self.points = 0;
+    }
+}
+
+/// Particles carried along by the field, leaving fading trails behind them.
+pub struct Particles {
+    rng: StdRng,
+    particles: Vec<Particle>,
+    /// Faded points and the paths of particles that have respawned since, kept for exporting
+    history: History,
+    // What the particles were spawned with, so that they can be spawned again if it changes
+    particle_count: usize,
+    particle_seeding: ParticleSeeding,
+    /// Whether the canvas was tiled last update, in which case trails wrap around its edges
+    tiled: bool,
+}
+
+impl Particles {
+    pub fn new(params: &VisualizerParams) -> Self {
+        let mut particles = Self {
+            rng: StdRng::seed_from_u64(params.seed as u64),
+            particles: Vec::new(),
+            history: History::new(PARTICLE_HISTORY_POINTS, PARTICLE_HISTORY_PATHS),
+            particle_count: params.settings.particle_count,
+            particle_seeding: params.settings.particle_seeding,
+            tiled: params.tiled,
+        };
+        particles.spawn();
+
+        particles
+    }
+
+    fn spawn(&mut self) {
+        self.particles = spawn_points(&mut self.rng, self.particle_count, self.particle_seeding)
+            .into_iter()
+            .map(Particle::new)
+            .collect();
+        self.history.clear();
+    }
+
+    fn random_point(rng: &mut StdRng) -> Point2<f64> {
+        [
+            rng.random_range(0.0..SCREEN_W as f64),
+            rng.random_range(0.0..SCREEN_H as f64),
+        ]
+    }
+}
+
+fn spawn_points(rng: &mut StdRng, count: usize, seeding: ParticleSeeding) -> Vec<Point2<f64>> {
+    let (width, height) = (SCREEN_W as f64, SCREEN_H as f64);
+
+    match seeding {
+        ParticleSeeding::Random => (0..count).map(|_| Particles::random_point(rng)).collect(),
+        ParticleSeeding::Grid => {
+            let columns = ((count as f64 * width / height).sqrt().round() as usize).max(1);
+            let rows = count.div_ceil(columns);
+            let (cell_w, cell_h) = (width / columns as f64, height / rows as f64);
+
+            (0..count)
+                .map(|i| {
+                    let (x, y) = ((i % columns) as f64, (i / columns) as f64);
+                    [x * cell_w + cell_w / 2.0, y * cell_h + cell_h / 2.0]
+                })
+                .collect()
+        }
+        ParticleSeeding::PoissonDisk => poisson_disk_points(rng, count),
+    }
+}
+
+/// Bridson's "Fast Poisson Disk Sampling in Arbitrary Dimensions", with the minimum distance
+/// picked so that the screen fits about `count` points.
+fn poisson_disk_points(rng: &mut StdRng, count: usize) -> Vec<Point2<f64>> {
+    // How many times to try placing a point around an active point before giving up on it
+    const ATTEMPTS: usize = 30;
+    let area = (SCREEN_W * SCREEN_H) as f64;
+    let radius = (0.7 * area / count.max(1) as f64).sqrt();
+    let mut grid = SpatialGrid::new(radius);
+
+    let first = Particles::random_point(rng);
+    grid.insert(first);
+    let mut points = vec![first];
+    let mut active = vec![first];
+
+    while !active.is_empty() && points.len() < count {
+        let i = rng.random_range(0..active.len());
+        let [x, y] = active[i];
+
+        let candidate = (0..ATTEMPTS).find_map(|_| {
+            let angle = rng.random_range(0.0..TAU);
+            let distance = rng.random_range(radius..radius * 2.0);
+            let p = [x + angle.cos() * distance, y + angle.sin() * distance];
+
+            (is_on_screen(p) && grid.is_clear(p, radius)).then_some(p)
+        });
+
+        match candidate {
+            Some(p) => {
+                grid.insert(p);
+                points.push(p);
+                active.push(p);
+            }
+            None => {
+                active.swap_remove(i);
+            }
+        }
+    }
+
+    points
+}
+
+impl Visualizer for Particles {
+    fn update(&mut self, params: &mut VisualizerParams) {
+        let settings = &params.settings;
+
+        if settings.particle_count != self.particle_count
+            || settings.particle_seeding != self.particle_seeding
+        {
+            self.particle_count = settings.particle_count;
+            self.particle_seeding = settings.particle_seeding;
+            self.spawn();
+        }
+
+        self.tiled = params.tiled;
+
+        for particle in self.particles.iter_mut().filter(|particle| particle.alive) {
+            let head = *particle.trail.back().unwrap();
+            let next = Integrator::Euler
+                .step(params, head, PARTICLE_SPEED)
+                .filter(|next| params.tiled || is_on_screen(*next));
+
+            match next {
+                Some(next) => {
+                    particle.trail.push_back(next);
+
+                    // Particles that leave a tiled canvas come back in on the other side, bringing
+                    // their trails with them
+                    let [wrapped_x, wrapped_y] = params.wrap(next);
+                    let [shift_x, shift_y] = [next[0] - wrapped_x, next[1] - wrapped_y];
+                    if shift_x != 0.0 || shift_y != 0.0 {
+                        for [x, y] in particle.trail.iter_mut().chain(particle.faded.iter_mut()) {
+                            *x -= shift_x;
+                            *y -= shift_y;
+                        }
+                    }
+                }
+                None if settings.respawn => {
+                    let spawn = match self.particle_seeding {
+                        ParticleSeeding::Random => Particles::random_point(&mut self.rng),
+                        ParticleSeeding::Grid | ParticleSeeding::PoissonDisk => particle.spawn,
+                    };
+                    self.history.push(particle.path());
+                    particle.trail = VecDeque::from([spawn]);
+                    particle.faded.clear();
+                }
+                None => particle.alive = false,
+            }
+
+            while particle.trail.len() > settings.trail_length {
+                particle.faded.extend(particle.trail.pop_front());
+            }
+
+            // The piece handed over ends where the particle's faded points carry on from
+            if particle.faded.len() >= FADED_PIECE_LENGTH {
+                let last = particle.faded[particle.faded.len() - 1];
+                self.history.push(std::mem::replace(&mut particle.faded, vec![last]));
+            }
+        }
+
+        // The grid visualizers move through time once per row, so keep pace with them
+        params.z_offset += params.noise_speed * GRID_SIZE_Y as f64;
+    }
+
+    /// Exports the paths the particles have taken, including the parts that have faded from the
+    /// screen and the paths of particles that have since respawned, as far back as `History`
+    /// keeps them.
+    fn build_svg_document_from_state(&self) -> svg::Document {
+        let paths: Vec<Vec<Point2<f64>>> = self
+            .history
+            .paths
+            .iter()
+            .cloned()
+            .chain(self.particles.iter().map(Particle::path))
+            .filter(|path| path.len() > 1)
+            .flat_map(|path| if self.tiled { wrap_polyline(&path) } else { vec![path] })
+            .collect();
+
+        build_svg_document_from_polylines(&paths)
+    }
+
+    fn render(&self) {
+        for particle in self.particles.iter() {
+            let segment_count = particle.trail.len().saturating_sub(1);
+
+            for (i, (&[x1, y1], &[x2, y2])) in particle
+                .trail
+                .iter()
+                .zip(particle.trail.iter().skip(1))
+                .enumerate()
+            {
+                // Older parts of the trail fade out
+                let alpha = (i + 1) as f32 / segment_count as f32;
+                let color = Color::new(1.0, 1.0, 1.0, alpha);
+                let segment = [[x1, y1], [x2, y2]];
+                let segments = if self.tiled { wrap_polyline(&segment) } else { vec![segment.to_vec()] };
+
+                for segment in segments {
+                    let [[x1, y1], [x2, y2]] = [segment[0], segment[segment.len() - 1]];
+                    draw_line(x1 as f32, y1 as f32, x2 as f32, y2 as f32, VECTOR_WIDTH as f32, color);
+                }
+            }
+        }
+    }
+
+    fn settings(&self) -> &'static [Setting] {
+        &[
+            Setting::ParticleCount,
+            Setting::ParticleSeeding,
+            Setting::TrailLength,
+            Setting::Respawn,
+        ]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Where each path in `history` starts along the x axis.
+    fn starts(history: &History) -> Vec<f64> {
+        history.paths.iter().map(|path| path[0][0]).collect()
+    }
+
+    #[test]
+    fn history_drops_the_oldest_paths_first() {
+        let mut history = History::new(10, 3);
+        for i in 0..4 {
+            history.push(vec![[i as f64, 0.0]; 4]);
+        }
+        // Four points each, so only the last two fit in ten points
+        assert_eq!(history.points, 8);
+        assert_eq!(starts(&history), [2.0, 3.0]);
+
+        let mut history = History::new(100, 3);
+        for i in 0..5 {
+            history.push(vec![[i as f64, 0.0]; 2]);
+        }
+        assert_eq!(starts(&history), [2.0, 3.0, 4.0]);
+
+        // Single points can't be drawn, so they aren't kept
+        history.push(vec![[9.0, 9.0]]);
+        assert_eq!(history.paths.len(), 3);
+    }
+
+    #[test]
+    fn history_stays_bounded() {
+        let mut params = VisualizerParams::default();
+        params.set_noise_fn(Box::new(noise::Perlin::new(0)));
+        params.settings.particle_count = 100;
+        params.settings.trail_length = 5;
+
+        for tiled in [false, true] {
+            params.tiled = tiled;
+            let mut particles = Particles::new(&params);
+            particles.history = History::new(2_000, 50);
+
+            for _ in 0..1_000 {
+                particles.update(&mut params);
+                assert!(particles.history.points <= 2_000);
+                assert!(particles.history.paths.len() <= 50);
+                assert!(particles.particles.iter().all(|particle| {
+                    particle.faded.len() < FADED_PIECE_LENGTH && particle.trail.len() <= 5
+                }));
+            }
+            assert!(!particles.history.paths.is_empty());
+        }
+    }
+}
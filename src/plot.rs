@@ -0,0 +1,699 @@
+use crate::{mask::Mask, streamlines::polyline_to_path};
+use std::f64::consts::TAU;
+use svg::node::{
+    element::path::{Command, Data, Position},
+    Node,
+};
+
+type Point2<T> = [T; 2];
+
+/// Endpoints closer together than this, in canvas pixels, count as touching.
+const TOUCHING_DISTANCE: f64 = 0.01;
+/// 2-opt stops early after this many passes over the paths, in case it's still finding tiny
+/// improvements.
+const MAX_TWO_OPT_PASSES: usize = 10;
+/// 2-opt only tries reversing runs of up to this many strokes. Nearest neighbour order mostly goes
+/// wrong locally, and checking every run would be quadratic in the number of paths.
+const TWO_OPT_SPAN: usize = 300;
+/// The longest a straight piece of a circle can be when it's turned into a polyline, in canvas
+/// pixels.
+const CIRCLE_SEGMENT_LENGTH: f64 = 2.0;
+
+/// Something the pen draws without lifting.
+#[derive(Clone)]
+enum Stroke {
+    /// Points the pen moves through in order. Polylines can be drawn backwards, and ones that
+    /// touch end to start are joined into one.
+    Polyline(Vec<Point2<f64>>),
+    /// A circle element with its centre and radius, which is kept as it is in SVGs. Plotters start
+    /// circles at their rightmost point, like they're drawn in SVG.
+    Circle(Box<dyn Node>, Point2<f64>, f64),
+}
+
+impl Stroke {
+    fn start(&self) -> Point2<f64> {
+        match self {
+            Stroke::Polyline(points) => points[0],
+            Stroke::Circle(_, [x, y], radius) => [x + radius, *y],
+        }
+    }
+
+    fn end(&self) -> Point2<f64> {
+        match self {
+            Stroke::Polyline(points) => points[points.len() - 1],
+            Stroke::Circle(..) => self.start(),
+        }
+    }
+
+    /// The points the pen moves through, with circles made out of short straight lines.
+    fn into_points(self) -> Vec<Point2<f64>> {
+        match self {
+            Stroke::Polyline(points) => points,
+            Stroke::Circle(_, [x, y], radius) => {
+                let segments = ((TAU * radius / CIRCLE_SEGMENT_LENGTH).ceil() as usize).max(8);
+
+                (0..=segments)
+                    .map(|i| {
+                        let angle = TAU * i as f64 / segments as f64;
+                        [x + radius * angle.cos(), y + radius * angle.sin()]
+                    })
+                    .collect()
+            }
+        }
+    }
+
+    fn reverse(&mut self) {
+        if let Stroke::Polyline(points) = self {
+            points.reverse();
+        }
+    }
+
+    fn into_node(self) -> Box<dyn Node> {
+        match self {
+            Stroke::Polyline(points) => Box::new(polyline_to_path(&points)),
+            Stroke::Circle(node, ..) => node,
+        }
+    }
+}
+
+/// How far the pen moves while it's lifted, in canvas pixels, before and after `order_paths`.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Travel {
+    pub before: f64,
+    pub after: f64,
+}
+
+/// Reorder the paths in every stroked group of an exported SVG so that a plotter wastes less time
+/// with the pen up. Paths are put in greedy nearest neighbour order, improved with 2-opt, drawn
+/// backwards when that's closer, and joined into one path where they touch.
+///
+/// Groups with anything in them other than plain lines, polylines, straight paths and circles are
+/// left alone, as are filled groups.
+pub fn order_paths(mut document: svg::Document) -> (svg::Document, Travel) {
+    let mut travel = Travel::default();
+
+    map_stroke_groups(&mut document, &mut |strokes, filled| {
+        if filled {
+            return None;
+        }
+
+        travel.before += pen_up_distance(&strokes);
+        let strokes = merge_touching(two_opt(nearest_neighbour(strokes)));
+        travel.after += pen_up_distance(&strokes);
+
+        Some(strokes)
+    });
+
+    (document, travel)
+}
+
+/// Split the strokes in `document` between `count` copies of it, one for each pen. `layer_of` is
+/// given the number of the stroke and a point on it, and picks the layer for that point. Polylines
+/// are cut wherever they go from one layer to another, while circles and filled shapes go to the
+/// layer of their first point.
+///
+/// Anything that isn't in a group `order_paths` could reorder, like the frame, stays on the first
+/// layer.
+pub fn split_layers(
+    mut document: svg::Document,
+    count: usize,
+    mut layer_of: impl FnMut(usize, Point2<f64>) -> usize,
+) -> Vec<svg::Document> {
+    let mut groups: Vec<Vec<Vec<Stroke>>> = Vec::new();
+    let mut stroke_count = 0;
+
+    map_stroke_groups(&mut document, &mut |strokes, filled| {
+        let mut layers = vec![Vec::new(); count];
+
+        for stroke in strokes {
+            let mut layer_at = |p| layer_of(stroke_count, p).min(count - 1);
+            match stroke {
+                Stroke::Polyline(points) if !filled => {
+                    for (layer, run) in split_polyline(points, &mut layer_at) {
+                        layers[layer].push(Stroke::Polyline(run));
+                    }
+                }
+                stroke => layers[layer_at(stroke.start())].push(stroke),
+            }
+            stroke_count += 1;
+        }
+
+        groups.push(layers);
+        None
+    });
+
+    (0..count)
+        .map(|layer| {
+            let mut document = document.clone();
+            let mut groups = groups.iter_mut();
+
+            if let Some(children) = document.get_children_mut() {
+                children.retain_mut(|child| {
+                    let is_stroke_group = map_stroke_groups(child.as_mut(), &mut |_, _| {
+                        groups
+                            .next()
+                            .map(|layers| std::mem::take(&mut layers[layer]))
+                    });
+                    layer == 0 || is_stroke_group
+                });
+            }
+
+            document
+        })
+        .collect()
+}
+
+/// Clip everything the pen draws in `document` to `mask`, cutting lines where they cross its edge
+/// so that nothing outside it gets plotted. Circles that cross the edge become polylines, and
+/// filled shapes are dropped unless they're entirely inside.
+///
+/// Filled cells and labels outside stroked groups, like the heatmap's, are dropped when the point
+/// they're placed at is outside the mask. Anything else, like the frame, is left as it is.
+pub fn clip_paths(mut document: svg::Document, mask: &Mask) -> svg::Document {
+    map_stroke_groups(&mut document, &mut |strokes, filled| {
+        Some(
+            strokes
+                .into_iter()
+                .flat_map(|stroke| clip_stroke(stroke, filled, mask))
+                .collect(),
+        )
+    });
+    drop_masked_fills(&mut document, mask);
+
+    document
+}
+
+fn clip_stroke(stroke: Stroke, filled: bool, mask: &Mask) -> Vec<Stroke> {
+    let points = stroke.clone().into_points();
+
+    if filled {
+        return match points.iter().all(|&p| mask.contains(p)) {
+            true => vec![stroke],
+            false => vec![],
+        };
+    }
+
+    let clipped = mask.clip(&points);
+    match (stroke, clipped.as_slice()) {
+        // Circles that aren't cut anywhere are kept as circles
+        (stroke @ Stroke::Circle(..), [inside]) if inside.len() == points.len() => vec![stroke],
+        _ => clipped.into_iter().map(Stroke::Polyline).collect(),
+    }
+}
+
+fn drop_masked_fills(node: &mut dyn Node, mask: &Mask) {
+    let Some(children) = node.get_children_mut() else {
+        return;
+    };
+
+    children.retain(|child| {
+        let Some(attributes) = child.get_attributes() else {
+            return true;
+        };
+        let filled = attributes.get("fill").is_some_and(|fill| &**fill != "none");
+        let number = |name: &str| {
+            attributes
+                .get(name)
+                .and_then(|value| value.parse::<f64>().ok())
+                .unwrap_or(0.0)
+        };
+        let anchor = match child.get_name() {
+            "rect" => [
+                number("x") + number("width") / 2.0,
+                number("y") + number("height") / 2.0,
+            ],
+            "text" => [number("x"), number("y")],
+            _ => return true,
+        };
+
+        !filled || mask.contains(anchor)
+    });
+
+    for child in children {
+        drop_masked_fills(child.as_mut(), mask);
+    }
+}
+
+/// Cut a polyline into runs of segments whose middles are on the same layer.
+fn split_polyline(
+    points: Vec<Point2<f64>>,
+    layer_at: &mut impl FnMut(Point2<f64>) -> usize,
+) -> Vec<(usize, Vec<Point2<f64>>)> {
+    let mut runs: Vec<(usize, Vec<Point2<f64>>)> = Vec::new();
+
+    for pair in points.windows(2) {
+        let [[x1, y1], [x2, y2]] = [pair[0], pair[1]];
+        let layer = layer_at([(x1 + x2) / 2.0, (y1 + y2) / 2.0]);
+
+        match runs.last_mut() {
+            Some((run_layer, run)) if *run_layer == layer => run.push(pair[1]),
+            _ => runs.push((layer, vec![pair[0], pair[1]])),
+        }
+    }
+
+    runs
+}
+
+/// Replace the strokes of every group under `node` made only of lines, polylines, straight paths
+/// and circles with what `f` returns for them, or leave them alone if it returns `None`. `f` is
+/// also told whether the group is filled. Returns whether there were any of these groups.
+fn map_stroke_groups(
+    node: &mut dyn Node,
+    f: &mut dyn FnMut(Vec<Stroke>, bool) -> Option<Vec<Stroke>>,
+) -> bool {
+    let name = node.get_name().to_owned();
+    let filled = node
+        .get_attributes()
+        .and_then(|attributes| attributes.get("fill"))
+        .is_some_and(|fill| &**fill != "none");
+    let Some(children) = node.get_children_mut() else {
+        return false;
+    };
+
+    let strokes: Option<Vec<Vec<Stroke>>> = match name.as_str() {
+        "g" => children
+            .iter()
+            .map(|child| strokes_of(child.as_ref()))
+            .collect(),
+        _ => None,
+    };
+
+    match strokes {
+        Some(strokes) if !strokes.is_empty() => {
+            if let Some(strokes) = f(strokes.into_iter().flatten().collect(), filled) {
+                *children = strokes.into_iter().map(Stroke::into_node).collect();
+            }
+            true
+        }
+        // Groups that can't be reordered might still have some that can inside them
+        _ => {
+            let mut found = false;
+            for child in children {
+                found |= map_stroke_groups(child.as_mut(), f);
+            }
+            found
+        }
+    }
+}
+
+/// Every line the pen draws in `document`, in order, as polylines in canvas pixels. Elements that
+/// aren't stroked, like the heatmap's cells, are skipped, and so is anything that isn't made of
+/// straight lines, rectangles and circles.
+pub fn pen_strokes(document: &svg::Document) -> Vec<Vec<Point2<f64>>> {
+    let mut polylines = Vec::new();
+
+    for child in document.get_children().into_iter().flatten() {
+        collect_pen_strokes(child.as_ref(), false, &mut polylines);
+    }
+
+    polylines
+}
+
+fn collect_pen_strokes(node: &dyn Node, stroked: bool, polylines: &mut Vec<Vec<Point2<f64>>>) {
+    let stroked = match node
+        .get_attributes()
+        .and_then(|attributes| attributes.get("stroke"))
+    {
+        Some(stroke) => &**stroke != "none",
+        None => stroked,
+    };
+
+    if node.get_name() == "g" {
+        for child in node.get_children().into_iter().flatten() {
+            collect_pen_strokes(child.as_ref(), stroked, polylines);
+        }
+    } else if stroked && let Some(strokes) = geometry_of(node) {
+        polylines.extend(strokes.into_iter().map(Stroke::into_points));
+    }
+}
+
+/// The strokes drawn by an element, or `None` if it isn't one that can be reordered. Elements
+/// with attributes of their own, like a colour, are left as they are.
+fn strokes_of(node: &dyn Node) -> Option<Vec<Stroke>> {
+    let attributes = node.get_attributes()?;
+    let geometry: &[&str] = match node.get_name() {
+        "line" => &["x1", "y1", "x2", "y2"],
+        "polyline" => &["points"],
+        "path" => &["d"],
+        "circle" => &["cx", "cy", "r"],
+        _ => return None,
+    };
+
+    if attributes
+        .keys()
+        .all(|key| geometry.contains(&key.as_str()))
+    {
+        geometry_of(node)
+    } else {
+        None
+    }
+}
+
+/// The strokes that make up an element's shape, or `None` if it isn't made of straight lines,
+/// rectangles and circles.
+fn geometry_of(node: &dyn Node) -> Option<Vec<Stroke>> {
+    let attributes = node.get_attributes()?;
+    let number = |name: &str| attributes.get(name)?.parse::<f64>().ok();
+    let number_or_zero = |name: &str| match attributes.get(name) {
+        Some(value) => value.parse::<f64>().ok(),
+        None => Some(0.0),
+    };
+
+    match node.get_name() {
+        "line" => Some(vec![Stroke::Polyline(vec![
+            [number("x1")?, number("y1")?],
+            [number("x2")?, number("y2")?],
+        ])]),
+        "rect" => {
+            let [x, y] = [number_or_zero("x")?, number_or_zero("y")?];
+            let [width, height] = [number("width")?, number("height")?];
+            Some(vec![Stroke::Polyline(vec![
+                [x, y],
+                [x + width, y],
+                [x + width, y + height],
+                [x, y + height],
+                [x, y],
+            ])])
+        }
+        "polyline" => {
+            let numbers = attributes
+                .get("points")?
+                .split(|c: char| c == ',' || c.is_whitespace())
+                .filter(|number| !number.is_empty())
+                .map(|number| number.parse::<f64>().ok())
+                .collect::<Option<Vec<_>>>()?;
+            let points: Vec<_> = numbers.chunks_exact(2).map(|xy| [xy[0], xy[1]]).collect();
+
+            (points.len() >= 2).then(|| vec![Stroke::Polyline(points)])
+        }
+        "path" => path_strokes(attributes.get("d")?),
+        "circle" => Some(vec![Stroke::Circle(
+            node.clone(),
+            [number("cx")?, number("cy")?],
+            number("r")?,
+        )]),
+        _ => None,
+    }
+}
+
+/// Split a path made of straight lines into one polyline per subpath.
+fn path_strokes(d: &str) -> Option<Vec<Stroke>> {
+    let data = Data::parse(d).ok()?;
+    let mut polylines: Vec<Vec<Point2<f64>>> = Vec::new();
+    let mut current = [0.0, 0.0];
+
+    for command in data.iter() {
+        let (position, parameters) = match command {
+            Command::Move(position, parameters) | Command::Line(position, parameters) => {
+                (position, parameters)
+            }
+            Command::Close => {
+                let polyline = polylines.last_mut()?;
+                current = polyline[0];
+                polyline.push(current);
+                continue;
+            }
+            // Curves would have to be flattened into lines first
+            _ => return None,
+        };
+
+        for (i, xy) in parameters.chunks_exact(2).enumerate() {
+            let [x, y] = [xy[0] as f64, xy[1] as f64];
+            current = match position {
+                Position::Absolute => [x, y],
+                Position::Relative => [current[0] + x, current[1] + y],
+            };
+
+            // Pairs after the first in a move are lines
+            if i == 0 && matches!(command, Command::Move(..)) {
+                polylines.push(vec![current]);
+            } else {
+                polylines.last_mut()?.push(current);
+            }
+        }
+    }
+
+    Some(
+        polylines
+            .into_iter()
+            .filter(|polyline| polyline.len() >= 2)
+            .map(Stroke::Polyline)
+            .collect(),
+    )
+}
+
+fn distance([x1, y1]: Point2<f64>, [x2, y2]: Point2<f64>) -> f64 {
+    (x2 - x1).hypot(y2 - y1)
+}
+
+/// How far the pen moves while it's lifted to draw `strokes` in order, starting from the top left
+/// corner.
+fn pen_up_distance(strokes: &[Stroke]) -> f64 {
+    let mut pen = [0.0, 0.0];
+    let mut total = 0.0;
+
+    for stroke in strokes {
+        total += distance(pen, stroke.start());
+        pen = stroke.end();
+    }
+
+    total
+}
+
+/// Start from the top left corner and keep drawing whichever stroke starts or ends closest to
+/// where the pen is.
+fn nearest_neighbour(mut remaining: Vec<Stroke>) -> Vec<Stroke> {
+    let mut ordered = Vec::with_capacity(remaining.len());
+    let mut pen = [0.0, 0.0];
+
+    while !remaining.is_empty() {
+        let mut nearest = (f64::INFINITY, 0, false);
+
+        for (i, stroke) in remaining.iter().enumerate() {
+            let to_start = distance(pen, stroke.start());
+            let to_end = distance(pen, stroke.end());
+
+            if to_start < nearest.0 {
+                nearest = (to_start, i, false);
+            }
+            if to_end < nearest.0 {
+                nearest = (to_end, i, true);
+            }
+        }
+
+        let (_, i, backwards) = nearest;
+        let mut stroke = remaining.swap_remove(i);
+        if backwards {
+            stroke.reverse();
+        }
+        pen = stroke.end();
+        ordered.push(stroke);
+    }
+
+    ordered
+}
+
+/// Improve an order by drawing runs of strokes backwards, in reverse order, wherever that makes
+/// the pen travel less.
+fn two_opt(mut strokes: Vec<Stroke>) -> Vec<Stroke> {
+    let n = strokes.len();
+    let mut starts: Vec<_> = strokes.iter().map(Stroke::start).collect();
+    let mut ends: Vec<_> = strokes.iter().map(Stroke::end).collect();
+
+    for _ in 0..MAX_TWO_OPT_PASSES {
+        let mut improved = false;
+
+        for i in 0..n {
+            let before = if i == 0 { [0.0, 0.0] } else { ends[i - 1] };
+
+            for j in i + 1..n.min(i + 1 + TWO_OPT_SPAN) {
+                // Reversing i..=j makes the run start at the end of j and finish at the start of i
+                let mut change = distance(before, ends[j]) - distance(before, starts[i]);
+                if j + 1 < n {
+                    change += distance(starts[i], starts[j + 1]) - distance(ends[j], starts[j + 1]);
+                }
+
+                if change < -1e-9 {
+                    strokes[i..=j].reverse();
+                    strokes[i..=j].iter_mut().for_each(Stroke::reverse);
+                    starts[i..=j].reverse();
+                    ends[i..=j].reverse();
+                    starts[i..=j].swap_with_slice(&mut ends[i..=j]);
+                    improved = true;
+                }
+            }
+        }
+
+        if !improved {
+            break;
+        }
+    }
+
+    strokes
+}
+
+/// Join polylines where one ends right where the next starts, so the pen doesn't lift between them.
+fn merge_touching(strokes: Vec<Stroke>) -> Vec<Stroke> {
+    let mut merged: Vec<Stroke> = Vec::with_capacity(strokes.len());
+
+    for stroke in strokes {
+        if let (Some(Stroke::Polyline(previous)), Stroke::Polyline(points)) =
+            (merged.last_mut(), &stroke)
+            && distance(previous[previous.len() - 1], points[0]) < TOUCHING_DISTANCE
+        {
+            previous.extend_from_slice(&points[1..]);
+            continue;
+        }
+
+        merged.push(stroke);
+    }
+
+    merged
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ::rand::{prelude::StdRng, Rng, SeedableRng};
+    use svg::node::element::{Circle, Group};
+
+    fn document(polylines: &[Vec<Point2<f64>>]) -> svg::Document {
+        svg::Document::new().add(stroked_group(polylines))
+    }
+
+    fn stroked_group(polylines: &[Vec<Point2<f64>>]) -> Group {
+        polylines.iter().fold(
+            Group::new().set("fill", "none").set("stroke", "black"),
+            |group, polyline| group.add(polyline_to_path(polyline)),
+        )
+    }
+
+    /// How far the pen travels with the pen up and down drawing `polylines` from the top left.
+    fn pen_distances(polylines: &[Vec<Point2<f64>>]) -> (f64, f64) {
+        let mut pen = [0.0, 0.0];
+        let (mut up, mut down) = (0.0, 0.0);
+
+        for polyline in polylines {
+            up += distance(pen, polyline[0]);
+            down += polyline.windows(2).map(|pair| distance(pair[0], pair[1])).sum::<f64>();
+            pen = polyline[polyline.len() - 1];
+        }
+
+        (up, down)
+    }
+
+    #[test]
+    fn paths_are_drawn_backwards_when_their_end_is_closer() {
+        let (document, travel) = order_paths(document(&[vec![[100.0, 0.0], [0.0, 0.0]]]));
+
+        assert_eq!(pen_strokes(&document), vec![vec![[0.0, 0.0], [100.0, 0.0]]]);
+        assert_eq!(travel, Travel { before: 100.0, after: 0.0 });
+    }
+
+    #[test]
+    fn touching_paths_are_joined() {
+        let polylines = [
+            vec![[20.0, 0.0], [30.0, 0.0]],
+            vec![[20.0, 0.0], [10.0, 0.0]],
+            vec![[0.0, 0.0], [10.0, 0.0]],
+            vec![[0.0, 50.0], [0.0, 60.0]],
+        ];
+        let (document, travel) = order_paths(document(&polylines));
+
+        assert_eq!(
+            pen_strokes(&document),
+            vec![
+                vec![[0.0, 0.0], [10.0, 0.0], [20.0, 0.0], [30.0, 0.0]],
+                vec![[0.0, 50.0], [0.0, 60.0]],
+            ]
+        );
+        assert_eq!(travel.after, 30.0_f64.hypot(50.0));
+    }
+
+    #[test]
+    fn closed_paths_stay_closed() {
+        let square = vec![[50.0, 0.0], [60.0, 0.0], [60.0, 10.0], [50.0, 10.0], [50.0, 0.0]];
+        let circle = Circle::new().set("cx", 10).set("cy", 100).set("r", 5);
+        let group = stroked_group(std::slice::from_ref(&square)).add(circle);
+
+        let (document, _) = order_paths(svg::Document::new().add(group));
+        let group = &document.get_children().unwrap()[0];
+        let children = group.get_children().unwrap();
+        let names: Vec<_> = children.iter().map(|child| child.get_name()).collect();
+        let strokes = pen_strokes(&document);
+
+        // The circle is kept as one, and neither shape is opened up or joined to the other
+        assert_eq!(names, ["path", "circle"]);
+        assert_eq!(strokes[0], square);
+        assert_eq!(strokes[1][0], strokes[1][strokes[1].len() - 1]);
+        assert_eq!(strokes[1][0], [15.0, 100.0]);
+    }
+
+    #[test]
+    fn clipping_cuts_lines_and_circles_at_the_mask() {
+        let mask: Mask = "circle 500 500 100".parse().unwrap();
+        let polylines = [
+            vec![[300.0, 500.0], [500.0, 500.0]],
+            vec![[450.0, 450.0], [550.0, 450.0]],
+            vec![[0.0, 0.0], [100.0, 0.0]],
+        ];
+        let inside = Circle::new().set("cx", 500).set("cy", 500).set("r", 10);
+        let crossing = Circle::new().set("cx", 600).set("cy", 500).set("r", 10);
+        let group = stroked_group(&polylines).add(inside).add(crossing);
+
+        let document = clip_paths(svg::Document::new().add(group), &mask);
+        let group = &document.get_children().unwrap()[0];
+        let children = group.get_children().unwrap();
+        let names: Vec<_> = children.iter().map(|child| child.get_name()).collect();
+        let strokes = pen_strokes(&document);
+
+        assert_eq!(names, ["path", "path", "circle", "path"]);
+        assert_eq!(strokes[0], vec![[400.0, 500.0], [500.0, 500.0]]);
+        assert_eq!(strokes[1], polylines[1]);
+        // Only the half of the crossing circle inside the mask is left
+        assert!(strokes[3].iter().all(|&[x, y]| (x - 500.0).hypot(y - 500.0) < 100.001));
+        assert!(strokes[3].iter().any(|&[x, _]| x < 595.0));
+    }
+
+    #[test]
+    fn filled_shapes_are_kept_only_when_inside_the_mask() {
+        let mut mask: Mask = "circle 500 500 100".parse().unwrap();
+        mask.set_inverted(true);
+        let squares = [
+            vec![[0.0, 0.0], [10.0, 0.0], [10.0, 10.0], [0.0, 0.0]],
+            vec![[495.0, 495.0], [505.0, 495.0], [505.0, 505.0], [495.0, 495.0]],
+            vec![[395.0, 495.0], [405.0, 495.0], [405.0, 505.0], [395.0, 495.0]],
+        ];
+        let group = stroked_group(&squares).set("fill", "black");
+
+        let document = clip_paths(svg::Document::new().add(group), &mask);
+
+        assert_eq!(pen_strokes(&document), squares[..1]);
+    }
+
+    #[test]
+    fn travel_never_increases() {
+        let mut rng = StdRng::seed_from_u64(7);
+
+        for count in [1, 2, 5, 50, 500] {
+            let polylines: Vec<Vec<_>> = (0..count)
+                .map(|_| {
+                    (0..rng.random_range(2..5))
+                        .map(|_| [rng.random_range(0..1920), rng.random_range(0..1080)])
+                        .map(|[x, y]| [x as f64, y as f64])
+                        .collect()
+                })
+                .collect();
+
+            let (document, travel) = order_paths(document(&polylines));
+            let (up_before, down_before) = pen_distances(&polylines);
+            let (up_after, down_after) = pen_distances(&pen_strokes(&document));
+
+            assert!(travel.after <= travel.before);
+            assert!((travel.before - up_before).abs() < 1e-6);
+            assert!((travel.after - up_after).abs() < 1e-6);
+            // Every line is still drawn
+            assert!((down_after - down_before).abs() < 1e-6);
+        }
+    }
+}
@@ -0,0 +1,241 @@
+use crate::page::PageSetup;
+use serde::{Deserialize, Serialize};
+use std::fmt::Write;
+
+type Point2<T> = [T; 2];
+
+/// HPGL coordinates are in plotter units of 0.025mm.
+const HPGL_UNITS_PER_MM: f64 = 40.0;
+
+/// Plotter files written next to every exported SVG, and how to drive the plotter in them.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PlotterSettings {
+    /// Write a `.gcode` file next to every exported SVG
+    pub gcode: bool,
+    /// Write an `.hpgl` file next to every exported SVG
+    pub hpgl: bool,
+    /// The G-code that lifts the pen off the paper
+    pub pen_up: String,
+    /// The G-code that puts the pen down on the paper
+    pub pen_down: String,
+    /// The G-code that waits for the pen to be changed between layers
+    pub pen_change: String,
+    /// How fast the pen moves while it's drawing, in mm per minute
+    pub draw_feed_rate: f64,
+    /// How fast the pen moves while it's lifted, in mm per minute
+    pub travel_feed_rate: f64,
+}
+
+impl PlotterSettings {
+    pub const FIELD_NAMES: [&str; 4] =
+        ["G-code export", "HPGL export", "draw feed rate", "travel feed rate"];
+    const MIN_FEED_RATE: f64 = 10.0;
+
+    /// Format the field at `field` (an index into `FIELD_NAMES`) for display.
+    pub fn field_value(&self, field: usize) -> String {
+        match field {
+            0 => self.gcode.to_string(),
+            1 => self.hpgl.to_string(),
+            2 => format!("{:.0} mm/min", self.draw_feed_rate),
+            3 => format!("{:.0} mm/min", self.travel_feed_rate),
+            _ => unreachable!(),
+        }
+    }
+
+    /// Nudge the field at `field` (an index into `FIELD_NAMES`) up or down by one step.
+    pub fn nudge(&mut self, field: usize, increase: bool) {
+        let factor = if increase { 1.1 } else { 1.0 / 1.1 };
+
+        match field {
+            0 => self.gcode = !self.gcode,
+            1 => self.hpgl = !self.hpgl,
+            2 => self.draw_feed_rate = (self.draw_feed_rate * factor).max(Self::MIN_FEED_RATE),
+            3 => self.travel_feed_rate = (self.travel_feed_rate * factor).max(Self::MIN_FEED_RATE),
+            _ => unreachable!(),
+        }
+    }
+}
+
+impl Default for PlotterSettings {
+    fn default() -> Self {
+        Self {
+            gcode: false,
+            hpgl: false,
+            pen_up: "G0 Z5".to_owned(),
+            pen_down: "G0 Z0".to_owned(),
+            pen_change: "M0".to_owned(),
+            draw_feed_rate: 3_000.0,
+            travel_feed_rate: 6_000.0,
+        }
+    }
+}
+
+/// Move `polylines` from the canvas onto the paper the way `page` lays out SVGs, in millimetres
+/// from the bottom left corner with y pointing up, which is how plotters count. Polylines of
+/// fewer than two points have nothing to draw, so they're left out.
+fn on_paper(polylines: &[Vec<Point2<f64>>], page: &PageSetup) -> Vec<Vec<Point2<f64>>> {
+    let (placement, [_, height]) = page.layout();
+
+    polylines
+        .iter()
+        .filter(|polyline| polyline.len() >= 2)
+        .map(|polyline| {
+            polyline
+                .iter()
+                .map(|&p| {
+                    let [x, y] = placement.apply(p);
+                    [x, height - y]
+                })
+                .collect()
+        })
+        .collect()
+}
+
+/// Write G-code that draws each layer of polylines in order, starting and finishing with the pen up
+/// at the origin. The plotter waits for the pen to be changed before each layer after the first,
+/// and layers are numbered in comments from `first_pen`. `description` goes in a comment at the
+/// top.
+pub fn gcode(
+    layers: &[Vec<Vec<Point2<f64>>>],
+    first_pen: usize,
+    page: &PageSetup,
+    settings: &PlotterSettings,
+    description: &str,
+) -> String {
+    let mut gcode = String::new();
+
+    // Writing to a String can't fail
+    let _ = writeln!(gcode, "; {description}");
+    let _ = writeln!(gcode, "G21 ; millimetres");
+    let _ = writeln!(gcode, "G90 ; absolute coordinates");
+    let _ = writeln!(gcode, "{}", settings.pen_up);
+
+    for (i, polylines) in layers.iter().enumerate() {
+        if i > 0 {
+            let _ = writeln!(gcode, "G0 X0 Y0 F{:.0}", settings.travel_feed_rate);
+            let _ = writeln!(
+                gcode,
+                "{} ; change to pen {}",
+                settings.pen_change,
+                first_pen + i
+            );
+        } else if layers.len() > 1 {
+            let _ = writeln!(gcode, "; pen {first_pen}");
+        }
+        write_gcode_polylines(&mut gcode, polylines, page, settings);
+    }
+
+    let _ = writeln!(gcode, "G0 X0 Y0 F{:.0}", settings.travel_feed_rate);
+
+    gcode
+}
+
+fn write_gcode_polylines(
+    gcode: &mut String,
+    polylines: &[Vec<Point2<f64>>],
+    page: &PageSetup,
+    settings: &PlotterSettings,
+) {
+    for polyline in on_paper(polylines, page) {
+        let [[x, y], rest @ ..] = polyline.as_slice() else {
+            continue;
+        };
+        let _ = writeln!(gcode, "G0 X{x:.3} Y{y:.3} F{:.0}", settings.travel_feed_rate);
+        let _ = writeln!(gcode, "{}", settings.pen_down);
+
+        for (i, [x, y]) in rest.iter().enumerate() {
+            if i == 0 {
+                let _ = writeln!(gcode, "G1 X{x:.3} Y{y:.3} F{:.0}", settings.draw_feed_rate);
+            } else {
+                let _ = writeln!(gcode, "G1 X{x:.3} Y{y:.3}");
+            }
+        }
+
+        let _ = writeln!(gcode, "{}", settings.pen_up);
+    }
+}
+
+/// Write HPGL that draws each layer of polylines in order, selecting pens counting up from
+/// `first_pen` for them.
+pub fn hpgl(layers: &[Vec<Vec<Point2<f64>>>], first_pen: usize, page: &PageSetup) -> String {
+    let mut hpgl = String::from("IN;\n");
+    let units = |[x, y]: Point2<f64>| {
+        format!(
+            "{},{}",
+            (x * HPGL_UNITS_PER_MM).round() as i64,
+            (y * HPGL_UNITS_PER_MM).round() as i64
+        )
+    };
+
+    for (i, polylines) in layers.iter().enumerate() {
+        let _ = writeln!(hpgl, "SP{};", first_pen + i);
+
+        for polyline in on_paper(polylines, page) {
+            let [start, rest @ ..] = polyline.as_slice() else {
+                continue;
+            };
+            let rest: Vec<String> = rest.iter().map(|&p| units(p)).collect();
+            let _ = writeln!(hpgl, "PU{};PD{};", units(*start), rest.join(","));
+        }
+    }
+
+    hpgl.push_str("PU0,0;SP0;\n");
+
+    hpgl
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Two layers on a screen sized page, where 96 canvas pixels are 25.4mm. The lone point has
+    /// nothing to draw.
+    fn layers() -> Vec<Vec<Vec<Point2<f64>>>> {
+        vec![
+            vec![vec![[0.0, 0.0], [96.0, 0.0], [96.0, 96.0]], vec![[10.0, 10.0]]],
+            vec![vec![[192.0, 1080.0], [0.0, 1080.0]]],
+        ]
+    }
+
+    #[test]
+    fn gcode_draws_each_layer_with_its_own_pen() {
+        let gcode = gcode(&layers(), 2, &PageSetup::default(), &PlotterSettings::default(), "test");
+
+        assert_eq!(
+            gcode,
+            "; test
+G21 ; millimetres
+G90 ; absolute coordinates
+G0 Z5
+; pen 2
+G0 X0.000 Y285.750 F6000
+G0 Z0
+G1 X25.400 Y285.750 F3000
+G1 X25.400 Y260.350
+G0 Z5
+G0 X0 Y0 F6000
+M0 ; change to pen 3
+G0 X50.800 Y0.000 F6000
+G0 Z0
+G1 X0.000 Y0.000 F3000
+G0 Z5
+G0 X0 Y0 F6000
+"
+        );
+    }
+
+    #[test]
+    fn hpgl_draws_each_layer_with_its_own_pen() {
+        assert_eq!(
+            hpgl(&layers(), 2, &PageSetup::default()),
+            "IN;
+SP2;
+PU0,11430;PD1016,11430,1016,10414;
+SP3;
+PU2032,0;PD0,0;
+PU0,0;SP0;
+"
+        );
+    }
+}
@@ -0,0 +1,190 @@
+use crate::{
+    consts::{DEFAULT_NOISE_SCALE, DEFAULT_NOISE_SPEED},
+    noise::{
+        new_noise_fn_by_index, DomainWarpingNoiseParams, FractalParams, NoiseGraph, NOISE_FN_COUNT,
+    },
+    layers::LayerSettings,
+    mask::Mask,
+    page::PageSetup,
+    plotter::PlotterSettings,
+    settings::VisualizerSettings,
+    field::{features_field, FieldMode, FlowFeature, Formula, SharedNoiseFn},
+    visualizer::{loop_length, VisualizerParams, VISUALIZER_COUNT},
+};
+use serde::{Deserialize, Serialize};
+use std::{error::Error, fs, path::Path, rc::Rc};
+
+/// Everything needed to recreate a scene. Saved to and loaded from TOML files.
+///
+/// Any field missing from a file falls back to its default value.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Preset {
+    pub noise: usize,
+    pub visualizer: usize,
+    pub seed: u32,
+    pub x_offset: f64,
+    pub y_offset: f64,
+    pub z_offset: f64,
+    pub scale: f64,
+    pub speed: f64,
+    /// How many frames it takes the animation to loop, or `None` if it never does
+    pub loop_period: Option<u32>,
+    /// Whether the field wraps around at the edges so that exports tile seamlessly
+    pub tiled: bool,
+    /// How exports are laid out on paper
+    pub page: PageSetup,
+    /// Plotter files exported along with SVGs
+    pub plotter: PlotterSettings,
+    /// How exports are split between pens
+    pub layers: LayerSettings,
+    /// The shape, text or image the visualization is limited to
+    pub mask: Option<Mask>,
+    /// Only used by the fractal noises. If it's missing, the noise crate's defaults are used.
+    pub fractal: Option<FractalParams>,
+    /// Only used by domain warping noise. If it's missing, random parameters from `seed` are used.
+    pub warp: Option<DomainWarpingNoiseParams>,
+    /// Replaces `noise`, `fractal` and `warp` when it's set
+    pub noise_graph: Option<NoiseGraph>,
+    pub settings: VisualizerSettings,
+    pub field_mode: FieldMode,
+    /// Only used by `FieldMode::Formula`
+    pub formula: Option<Formula>,
+    /// Analytic flows added on top of the noise's field
+    pub features: Vec<FlowFeature>,
+}
+
+impl Preset {
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, Box<dyn Error>> {
+        let path = path.as_ref();
+        let contents = fs::read_to_string(path)
+            .map_err(|err| format!("couldn't read preset {}: {err}", path.display()))?;
+        let preset: Preset = toml::from_str(&contents)
+            .map_err(|err| format!("couldn't parse preset {}: {err}", path.display()))?;
+
+        if preset.noise >= NOISE_FN_COUNT {
+            return Err(format!("preset noise must be less than {NOISE_FN_COUNT}").into());
+        }
+
+        if preset.field_mode == FieldMode::Formula && preset.formula.is_none() {
+            return Err("preset uses the formula field mode but has no formula".into());
+        }
+
+        if preset.loop_period == Some(0) {
+            return Err("preset loop_period must be at least 1 frame".into());
+        }
+
+        preset.page.validate()?;
+
+        if !(1..=LayerSettings::MAX_PENS).contains(&preset.layers.pens) {
+            let message = format!("preset layers.pens must be from 1 to {}", LayerSettings::MAX_PENS);
+            return Err(message.into());
+        }
+
+        if preset.visualizer >= VISUALIZER_COUNT {
+            return Err(format!("preset visualizer must be less than {VISUALIZER_COUNT}").into());
+        }
+
+        Ok(preset)
+    }
+
+    /// Follow `formula` instead of the noise's own field.
+    pub fn use_formula(&mut self, formula: Formula) {
+        self.formula = Some(formula);
+        self.field_mode = FieldMode::Formula;
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), Box<dyn Error>> {
+        fs::write(path, toml::to_string_pretty(self)?)?;
+
+        Ok(())
+    }
+
+    pub fn to_params(&self) -> VisualizerParams {
+        let loop_length = loop_length(self.loop_period, self.speed);
+        let (fractal, warp, noise_fn) = match &self.noise_graph {
+            Some(noise_graph) => (None, None, noise_graph.build(self.seed, loop_length)),
+            None => {
+                let warp = DomainWarpingNoiseParams::for_index(self.noise, self.seed)
+                    .map(|default_warp| self.warp.clone().unwrap_or(default_warp));
+                let fractal = FractalParams::default_for_noise(self.noise, warp.as_ref())
+                    .map(|default_fractal| self.fractal.unwrap_or(default_fractal));
+                let noise_fn = new_noise_fn_by_index(
+                    self.noise,
+                    self.seed,
+                    fractal.as_ref(),
+                    warp.as_ref(),
+                    loop_length,
+                );
+                (fractal, warp, noise_fn)
+            }
+        };
+        let noise_fn: SharedNoiseFn = Rc::from(noise_fn);
+
+        VisualizerParams {
+            base_x_offset: self.x_offset,
+            base_y_offset: self.y_offset,
+            noise_scale: self.scale,
+            noise_speed: self.speed,
+            field: self
+                .field_mode
+                .field(noise_fn.clone(), self.formula.as_ref(), self.seed, loop_length),
+            formula: self.formula.clone(),
+            features: self.features.clone(),
+            feature_field: features_field(&self.features),
+            noise_fn,
+            seed: self.seed,
+            fractal,
+            warp,
+            noise_graph: self.noise_graph.clone(),
+            settings: self.settings.clone(),
+            field_mode: self.field_mode,
+            z_offset: self.z_offset,
+            loop_period: self.loop_period,
+            tiled: self.tiled,
+            page: self.page,
+            plotter: self.plotter.clone(),
+            layers: self.layers.clone(),
+            mask: self.mask.clone(),
+        }
+    }
+}
+
+impl Default for Preset {
+    fn default() -> Self {
+        Self {
+            noise: 0,
+            visualizer: 0,
+            seed: 0,
+            x_offset: 0.0,
+            y_offset: 0.0,
+            z_offset: 0.0,
+            scale: DEFAULT_NOISE_SCALE,
+            speed: DEFAULT_NOISE_SPEED,
+            loop_period: None,
+            tiled: false,
+            page: PageSetup::default(),
+            plotter: PlotterSettings::default(),
+            layers: LayerSettings::default(),
+            mask: None,
+            fractal: None,
+            warp: None,
+            noise_graph: None,
+            settings: VisualizerSettings::default(),
+            field_mode: FieldMode::default(),
+            formula: None,
+            features: Vec::new(),
+        }
+    }
+}
+
+/// Find the most recently modified preset in `base_path`.
+pub fn latest_preset_filepath(base_path: impl AsRef<Path>) -> Option<std::path::PathBuf> {
+    fs::read_dir(base_path)
+        .ok()?
+        .filter_map(Result::ok)
+        .filter(|entry| entry.path().extension().is_some_and(|ext| ext == "toml"))
+        .filter_map(|entry| Some((entry.metadata().ok()?.modified().ok()?, entry.path())))
+        .max_by_key(|(modified, _)| *modified)
+        .map(|(_, path)| path)
+}
@@ -0,0 +1,418 @@
+use crate::{colormap::Colormap, field::FlowKind};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// How many CSS pixels fit in a millimetre. This is how SVG viewers size a document that doesn't
+/// specify its own physical size.
+pub const PX_PER_MM: f64 = 96.0 / 25.4;
+
+/// A distance on the canvas, in either screen pixels or millimetres on the paper the export is laid
+/// out on. Without paper, millimetres are as big as SVG viewers show them.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Length {
+    Px(f64),
+    Mm(f64),
+}
+
+impl Length {
+    /// The distance in canvas pixels, when each pixel comes out `mm_per_px` millimetres wide on
+    /// paper.
+    pub fn to_px(self, mm_per_px: f64) -> f64 {
+        match self {
+            Length::Px(px) => px,
+            Length::Mm(mm) => mm / mm_per_px,
+        }
+    }
+
+    fn scaled(self, factor: f64) -> Self {
+        match self {
+            Length::Px(px) => Length::Px(px * factor),
+            Length::Mm(mm) => Length::Mm(mm * factor),
+        }
+    }
+
+    /// The same distance in the other unit.
+    fn with_other_unit(self, mm_per_px: f64) -> Self {
+        match self {
+            Length::Px(px) => Length::Mm(px * mm_per_px),
+            Length::Mm(mm) => Length::Px(mm / mm_per_px),
+        }
+    }
+}
+
+impl fmt::Display for Length {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Length::Px(px) => write!(f, "{px:.1}px"),
+            Length::Mm(mm) => write!(f, "{mm:.2}mm"),
+        }
+    }
+}
+
+/// A setting with a fixed list of options that PgUp and PgDn step through.
+pub trait Choice: Copy + PartialEq + 'static {
+    const ALL: &'static [Self];
+
+    fn cycled(self, forward: bool) -> Self {
+        let len = Self::ALL.len();
+        let i = Self::ALL.iter().position(|choice| *choice == self).unwrap();
+
+        if forward {
+            Self::ALL[(i + 1) % len]
+        } else {
+            Self::ALL[(i + len - 1) % len]
+        }
+    }
+}
+
+/// Where particles start out.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ParticleSeeding {
+    Random,
+    Grid,
+    PoissonDisk,
+}
+
+impl Choice for ParticleSeeding {
+    const ALL: &'static [Self] = &[
+        ParticleSeeding::Random,
+        ParticleSeeding::Grid,
+        ParticleSeeding::PoissonDisk,
+    ];
+}
+
+impl fmt::Display for ParticleSeeding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ParticleSeeding::Random => write!(f, "random"),
+            ParticleSeeding::Grid => write!(f, "grid"),
+            ParticleSeeding::PoissonDisk => write!(f, "Poisson disk"),
+        }
+    }
+}
+
+/// How arrow heads are drawn.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ArrowHead {
+    Open,
+    Filled,
+}
+
+impl Choice for ArrowHead {
+    const ALL: &'static [Self] = &[ArrowHead::Open, ArrowHead::Filled];
+}
+
+impl fmt::Display for ArrowHead {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ArrowHead::Open => write!(f, "open"),
+            ArrowHead::Filled => write!(f, "filled"),
+        }
+    }
+}
+
+/// What decides how strong the field is at a point.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MagnitudeSource {
+    /// Every point is equally strong
+    Constant,
+    /// How far the noise is from zero
+    NoiseAmplitude,
+    /// How quickly the noise is changing
+    GradientNorm,
+    /// How long the field's vector is
+    FieldStrength,
+}
+
+impl Choice for MagnitudeSource {
+    const ALL: &'static [Self] = &[
+        MagnitudeSource::Constant,
+        MagnitudeSource::NoiseAmplitude,
+        MagnitudeSource::GradientNorm,
+        MagnitudeSource::FieldStrength,
+    ];
+}
+
+impl fmt::Display for MagnitudeSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MagnitudeSource::Constant => write!(f, "constant"),
+            MagnitudeSource::NoiseAmplitude => write!(f, "noise amplitude"),
+            MagnitudeSource::GradientNorm => write!(f, "gradient norm"),
+            MagnitudeSource::FieldStrength => write!(f, "field strength"),
+        }
+    }
+}
+
+/// Which part of an arrow grows with the magnitude.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MagnitudeTarget {
+    Length,
+    HeadSize,
+}
+
+impl Choice for MagnitudeTarget {
+    const ALL: &'static [Self] = &[MagnitudeTarget::Length, MagnitudeTarget::HeadSize];
+}
+
+impl fmt::Display for MagnitudeTarget {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MagnitudeTarget::Length => write!(f, "length"),
+            MagnitudeTarget::HeadSize => write!(f, "head size"),
+        }
+    }
+}
+
+/// Something that can be selected with Tab and changed with PgUp and PgDn.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Setting {
+    /// A field of the active noise's `FractalParams`, by index into `FractalParams::FIELD_NAMES`
+    Fractal(usize),
+    /// A setting of the active `NoiseGraph`, counting through its nodes parents first
+    NoiseGraph(usize),
+    /// Which preset the active domain warping noise's parameters come from
+    WarpPreset,
+    /// A field of the active `DomainWarpingNoiseParams`, by index up to its `FIELD_COUNT`
+    Warp(usize),
+    /// How many frames a looping animation takes, which lives on `VisualizerParams`
+    LoopPeriod,
+    /// A field of the export's `PageSetup`, by index into `PageSetup::FIELD_NAMES`
+    Page(usize),
+    /// A field of the export's `PlotterSettings`, by index into `PlotterSettings::FIELD_NAMES`
+    Plotter(usize),
+    /// A field of the export's `LayerSettings`, by index into `LayerSettings::FIELD_NAMES`
+    Layers(usize),
+    /// Whether the mask on `VisualizerParams` keeps what's outside its shape instead
+    InvertMask,
+    /// A field of the last `FlowFeature` placed, by index into `FlowFeature::FIELD_NAMES`
+    Feature(usize),
+    Separation,
+    SeparationUnit,
+    TestDistance,
+    ParticleCount,
+    ParticleSeeding,
+    TrailLength,
+    Respawn,
+    ArrowHead,
+    MagnitudeSource,
+    MagnitudeTarget,
+    Colormap,
+    Legend,
+    ContourLevels,
+    FeatureKind,
+    NoiseWeight,
+}
+
+/// Settings used by individual visualizers. Each visualizer lists the ones it uses with
+/// `Visualizer::settings`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct VisualizerSettings {
+    /// The minimum distance between evenly spaced streamlines
+    pub separation: Length,
+    /// Evenly spaced streamlines stop when they get this close to another line, as a fraction of
+    /// `separation`
+    pub test_distance: f64,
+    pub particle_count: usize,
+    pub particle_seeding: ParticleSeeding,
+    /// How many points of each particle's trail are kept
+    pub trail_length: usize,
+    /// Whether particles that leave the screen start over
+    pub respawn: bool,
+    pub arrow_head: ArrowHead,
+    pub magnitude_source: MagnitudeSource,
+    pub magnitude_target: MagnitudeTarget,
+    pub colormap: Colormap,
+    /// Whether to show a bar with the range of values next to a heatmap
+    pub legend: bool,
+    /// How many evenly spaced contours to draw
+    pub contour_count: usize,
+    /// The exact noise values to draw contours at. Overrides `contour_count` unless it's empty.
+    pub contour_levels: Vec<f64>,
+    /// What kind of analytic flow gets placed at the mouse
+    pub feature_kind: FlowKind,
+    /// How much of the noise's field is kept under the analytic flows. At 1 the flows are added
+    /// to it, at 0 only the flows are left.
+    pub noise_weight: f64,
+}
+
+impl VisualizerSettings {
+    pub const MIN_SEPARATION_PX: f64 = 2.0;
+    const MAX_PARTICLE_COUNT: usize = 20_000;
+    const MAX_TRAIL_LENGTH: usize = 5_000;
+    const MAX_CONTOUR_COUNT: usize = 200;
+
+    pub fn name(setting: Setting) -> &'static str {
+        match setting {
+            Setting::Fractal(_) => unreachable!("fractal settings are named by FractalParams"),
+            Setting::NoiseGraph(_) => unreachable!("noise graph settings are named by NoiseGraph"),
+            Setting::WarpPreset | Setting::Warp(_) => {
+                unreachable!("warp settings are named by DomainWarpingNoiseParams")
+            }
+            Setting::LoopPeriod => "loop period",
+            Setting::Page(_) => unreachable!("page settings are named by PageSetup"),
+            Setting::Plotter(_) => unreachable!("plotter settings are named by PlotterSettings"),
+            Setting::Layers(_) => unreachable!("layer settings are named by LayerSettings"),
+            Setting::InvertMask => "invert mask",
+            Setting::Feature(_) => unreachable!("flow settings are named by FlowFeature"),
+            Setting::Separation => "separation",
+            Setting::SeparationUnit => "separation unit",
+            Setting::TestDistance => "test distance",
+            Setting::ParticleCount => "particles",
+            Setting::ParticleSeeding => "particle seeding",
+            Setting::TrailLength => "trail length",
+            Setting::Respawn => "respawn",
+            Setting::ArrowHead => "arrow head",
+            Setting::MagnitudeSource => "magnitude",
+            Setting::MagnitudeTarget => "magnitude sets",
+            Setting::Colormap => "colormap",
+            Setting::Legend => "legend",
+            Setting::ContourLevels => "contours",
+            Setting::FeatureKind => "place",
+            Setting::NoiseWeight => "noise weight",
+        }
+    }
+
+    /// Format a setting's current value for display.
+    pub fn value(&self, setting: Setting) -> String {
+        match setting {
+            Setting::Fractal(_) => unreachable!("fractal settings live on FractalParams"),
+            Setting::NoiseGraph(_) => unreachable!("noise graph settings live on NoiseGraph"),
+            Setting::WarpPreset | Setting::Warp(_) => {
+                unreachable!("warp settings live on DomainWarpingNoiseParams")
+            }
+            Setting::LoopPeriod => unreachable!("the loop period lives on VisualizerParams"),
+            Setting::Page(_) => unreachable!("page settings live on PageSetup"),
+            Setting::Plotter(_) => unreachable!("plotter settings live on PlotterSettings"),
+            Setting::Layers(_) => unreachable!("layer settings live on LayerSettings"),
+            Setting::InvertMask => unreachable!("the mask lives on VisualizerParams"),
+            Setting::Feature(_) => unreachable!("flows live on VisualizerParams"),
+            Setting::Separation => self.separation.to_string(),
+            Setting::SeparationUnit => match self.separation {
+                Length::Px(_) => "px".to_owned(),
+                Length::Mm(_) => "mm".to_owned(),
+            },
+            Setting::TestDistance => format!("{:.2}", self.test_distance),
+            Setting::ParticleCount => self.particle_count.to_string(),
+            Setting::ParticleSeeding => self.particle_seeding.to_string(),
+            Setting::TrailLength => self.trail_length.to_string(),
+            Setting::Respawn => self.respawn.to_string(),
+            Setting::ArrowHead => self.arrow_head.to_string(),
+            Setting::MagnitudeSource => self.magnitude_source.to_string(),
+            Setting::MagnitudeTarget => self.magnitude_target.to_string(),
+            Setting::Colormap => self.colormap.to_string(),
+            Setting::Legend => self.legend.to_string(),
+            Setting::ContourLevels => match self.contour_levels.len() {
+                0 => self.contour_count.to_string(),
+                len => format!("{len} custom levels"),
+            },
+            Setting::FeatureKind => self.feature_kind.to_string(),
+            Setting::NoiseWeight => format!("{:.1}", self.noise_weight),
+        }
+    }
+
+    /// Nudge a setting up or down by one step. Lengths in millimetres are measured on paper where
+    /// each canvas pixel is `mm_per_px` millimetres wide.
+    pub fn nudge(&mut self, setting: Setting, increase: bool, mm_per_px: f64) {
+        let sign = if increase { 1.0 } else { -1.0 };
+
+        match setting {
+            Setting::Fractal(_) => unreachable!("fractal settings live on FractalParams"),
+            Setting::NoiseGraph(_) => unreachable!("noise graph settings live on NoiseGraph"),
+            Setting::WarpPreset | Setting::Warp(_) => {
+                unreachable!("warp settings live on DomainWarpingNoiseParams")
+            }
+            Setting::LoopPeriod => unreachable!("the loop period lives on VisualizerParams"),
+            Setting::Page(_) => unreachable!("page settings live on PageSetup"),
+            Setting::Plotter(_) => unreachable!("plotter settings live on PlotterSettings"),
+            Setting::Layers(_) => unreachable!("layer settings live on LayerSettings"),
+            Setting::InvertMask => unreachable!("the mask lives on VisualizerParams"),
+            Setting::Feature(_) => unreachable!("flows live on VisualizerParams"),
+            Setting::Separation => {
+                let separation = self.separation.scaled(if increase { 1.1 } else { 1.0 / 1.1 });
+                if separation.to_px(mm_per_px) >= Self::MIN_SEPARATION_PX {
+                    self.separation = separation;
+                }
+            }
+            Setting::SeparationUnit => {
+                self.separation = self.separation.with_other_unit(mm_per_px)
+            }
+            Setting::TestDistance => {
+                self.test_distance = (self.test_distance + sign * 0.05).clamp(0.05, 1.0)
+            }
+            Setting::ParticleCount => {
+                self.particle_count = nudge_count(self.particle_count, increase)
+                    .clamp(1, Self::MAX_PARTICLE_COUNT)
+            }
+            Setting::ParticleSeeding => {
+                self.particle_seeding = self.particle_seeding.cycled(increase)
+            }
+            Setting::TrailLength => {
+                self.trail_length =
+                    nudge_count(self.trail_length, increase).clamp(2, Self::MAX_TRAIL_LENGTH)
+            }
+            Setting::Respawn => self.respawn = !self.respawn,
+            Setting::ArrowHead => self.arrow_head = self.arrow_head.cycled(increase),
+            Setting::MagnitudeSource => {
+                self.magnitude_source = self.magnitude_source.cycled(increase)
+            }
+            Setting::MagnitudeTarget => {
+                self.magnitude_target = self.magnitude_target.cycled(increase)
+            }
+            Setting::Colormap => self.colormap = self.colormap.cycled(increase),
+            Setting::Legend => self.legend = !self.legend,
+            Setting::ContourLevels if !self.contour_levels.is_empty() => {
+                // Switch back to evenly spaced contours, starting with the same number of them
+                self.contour_count = self.contour_levels.len();
+                self.contour_levels.clear();
+            }
+            Setting::ContourLevels => {
+                self.contour_count =
+                    nudge_count(self.contour_count, increase).clamp(1, Self::MAX_CONTOUR_COUNT)
+            }
+            Setting::FeatureKind => self.feature_kind = self.feature_kind.cycled(increase),
+            Setting::NoiseWeight => {
+                self.noise_weight = (self.noise_weight + sign * 0.1).clamp(0.0, 1.0)
+            }
+        }
+    }
+}
+
+impl Default for VisualizerSettings {
+    fn default() -> Self {
+        Self {
+            separation: Length::Px(12.0),
+            test_distance: 0.5,
+            particle_count: 2_000,
+            particle_seeding: ParticleSeeding::Random,
+            trail_length: 50,
+            respawn: true,
+            arrow_head: ArrowHead::Open,
+            magnitude_source: MagnitudeSource::NoiseAmplitude,
+            magnitude_target: MagnitudeTarget::Length,
+            colormap: Colormap::Viridis,
+            legend: true,
+            contour_count: 10,
+            contour_levels: Vec::new(),
+            feature_kind: FlowKind::Vortex,
+            noise_weight: 1.0,
+        }
+    }
+}
+
+/// Grow or shrink a count by about a quarter, always changing it by at least one.
+fn nudge_count(count: usize, increase: bool) -> usize {
+    let step = (count / 4).max(1);
+
+    if increase {
+        count.saturating_add(step)
+    } else {
+        count.saturating_sub(step)
+    }
+}
@@ -0,0 +1,141 @@
+use crate::consts::{SCREEN_H, SCREEN_W};
+
+type Point2<T> = [T; 2];
+
+/// Buckets points into square cells so that distance checks only look at nearby points.
+pub struct SpatialGrid {
+    cell_size: f64,
+    columns: usize,
+    rows: usize,
+    cells: Vec<Vec<Point2<f64>>>,
+    /// Whether distances are measured across the edges of the screen, as on a tiled canvas
+    wraps: bool,
+}
+
+impl SpatialGrid {
+    pub fn new(cell_size: f64) -> Self {
+        let columns = (SCREEN_W as f64 / cell_size).ceil() as usize + 1;
+        let rows = (SCREEN_H as f64 / cell_size).ceil() as usize + 1;
+
+        Self {
+            cell_size,
+            columns,
+            rows,
+            cells: vec![Vec::new(); columns * rows],
+            wraps: false,
+        }
+    }
+
+    /// Measure distances across the edges of the screen when `wraps` is set, so that points on
+    /// either side of a seam of a tiled canvas count as close. Points must already be wrapped
+    /// onto the screen.
+    pub fn wrapping(self, wraps: bool) -> Self {
+        if !wraps {
+            return self;
+        }
+
+        // No spare column or row, so that the cells line up with the edges of the screen
+        let columns = (SCREEN_W as f64 / self.cell_size).ceil() as usize;
+        let rows = (SCREEN_H as f64 / self.cell_size).ceil() as usize;
+
+        Self {
+            columns,
+            rows,
+            cells: vec![Vec::new(); columns * rows],
+            wraps,
+            ..self
+        }
+    }
+
+    fn cell_of(&self, [x, y]: Point2<f64>) -> (usize, usize) {
+        let column = ((x / self.cell_size).max(0.0) as usize).min(self.columns - 1);
+        let row = ((y / self.cell_size).max(0.0) as usize).min(self.rows - 1);
+
+        (column, row)
+    }
+
+    /// The index of the cell `[dx, dy]` cells away from `(column, row)`, if there is one.
+    fn neighbour(&self, (column, row): (usize, usize), [dx, dy]: [isize; 2]) -> Option<usize> {
+        let [column, row] = [column as isize + dx, row as isize + dy];
+        let [columns, rows] = [self.columns as isize, self.rows as isize];
+
+        if self.wraps {
+            return Some((column.rem_euclid(columns) + row.rem_euclid(rows) * columns) as usize);
+        }
+
+        ((0..columns).contains(&column) && (0..rows).contains(&row))
+            .then_some((column + row * columns) as usize)
+    }
+
+    pub fn insert(&mut self, p: Point2<f64>) {
+        let (column, row) = self.cell_of(p);
+        self.cells[column + row * self.columns].push(p);
+    }
+
+    /// Check that no point in the grid is closer to `p` than `distance`.
+    pub fn is_clear(&self, p: Point2<f64>, distance: f64) -> bool {
+        let cell = self.cell_of(p);
+        // When wrapping, the last column and row can be narrower than the rest, which leaves close
+        // points across the seam a cell further away than they'd otherwise be
+        let reach = (distance / self.cell_size).ceil() as isize + isize::from(self.wraps);
+        let distance_squared = distance * distance;
+        let (w, h) = (SCREEN_W as f64, SCREEN_H as f64);
+
+        for dy in -reach..=reach {
+            for dx in -reach..=reach {
+                let Some(index) = self.neighbour(cell, [dx, dy]) else {
+                    continue;
+                };
+
+                let too_close = self.cells[index].iter().any(|q| {
+                    let (mut dx, mut dy) = (q[0] - p[0], q[1] - p[1]);
+                    // Measure to whichever copy of `q` is nearest
+                    if self.wraps {
+                        dx -= w * (dx / w).round();
+                        dy -= h * (dy / h).round();
+                    }
+                    dx * dx + dy * dy < distance_squared
+                });
+
+                if too_close {
+                    return false;
+                }
+            }
+        }
+
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn points_across_the_seams_are_close() {
+        // 50 doesn't divide 1920 or 1080, so the last column and row are narrower than the rest
+        let mut grid = SpatialGrid::new(50.0).wrapping(true);
+        grid.insert([1899.0, 500.0]);
+        grid.insert([500.0, 1049.0]);
+        grid.insert([1919.0, 1079.0]);
+
+        assert!(!grid.is_clear([0.1, 500.0], 45.0));
+        assert!(!grid.is_clear([500.0, 0.1], 45.0));
+        assert!(!grid.is_clear([1.0, 1.0], 5.0));
+        assert!(grid.is_clear([0.1, 500.0], 20.0));
+        assert!(grid.is_clear([500.0, 0.1], 30.0));
+        assert!(grid.is_clear([960.0, 540.0], 45.0));
+    }
+
+    #[test]
+    fn points_across_the_edges_are_far_without_wrapping() {
+        let mut grid = SpatialGrid::new(50.0);
+        grid.insert([1899.0, 500.0]);
+        grid.insert([10.0, 10.0]);
+
+        assert!(grid.is_clear([0.1, 500.0], 45.0));
+        assert!(!grid.is_clear([1899.0, 540.0], 45.0));
+        assert!(!grid.is_clear([0.0, 0.0], 15.0));
+        assert!(grid.is_clear([0.0, 0.0], 14.0));
+    }
+}
@@ -1,39 +1,78 @@
 use crate::{
-    circles::Circles,
     consts::{
-        DEFAULT_MOVE_SPEED, DEFAULT_NOISE_SCALE, DEFAULT_NOISE_SCALE_INCREMENT,
+        DEFAULT_LOOP_PERIOD, DEFAULT_MOVE_SPEED, DEFAULT_NOISE_SCALE, DEFAULT_NOISE_SCALE_INCREMENT,
         DEFAULT_NOISE_SPEED, DEFAULT_NOISE_SPEED_INCREMENT,
     },
     counter::Counter,
-    line_segments::LineSegments,
-    noise::new_noise_fn_by_index,
-    visualizer::{Visualizer, VisualizerParams},
+    export::{save_export, unused_filepath, unused_svg_filepath},
+    field::{FieldMode, FlowFeature},
+    noise::{
+        new_noise_fn_by_index, DomainWarpingNoiseParams, FractalParams, WarpPreset, NOISE_FN_COUNT,
+        NOISE_FN_NAMES,
+    },
+    layers::LayerSettings,
+    page::PageSetup,
+    plotter::PlotterSettings,
+    preset::{latest_preset_filepath, Preset},
+    settings::{Choice, Setting, VisualizerSettings},
+    visualizer::{
+        new_visualizer_by_index, Visualizer, VisualizerParams, VISUALIZER_COUNT, VISUALIZER_NAMES,
+    },
 };
-use chrono::Local;
-use log::{error, info, warn};
-use std::path::PathBuf;
+use log::{error, info};
 use macroquad::miniquad::window::quit;
 use macroquad::prelude::*;
 
+const HELP_LINES: &[&str] = &[
+    "N | B       Cycle forward and back through Noise types",
+    "J | K       Change the visualizer kind (lines, circles, streamlines, particles, arrows...)",
+    "+ | -       Zoom in and out by changing the \"scale\" of the noise",
+    "] | [       Speed up or slow down the rate of change",
+    "T           Loop time around so the animation repeats, or let it run on",
+    "E           Wrap the field around the edges so that it tiles seamlessly",
+    ". | ,       Step the seed forward and back",
+    "S           Pick a random seed",
+    "W           Re-roll the domain warp offsets, keeping the seed",
+    "G           Turn noise into directions by angle, gradient, iso-lines or curl",
+    "F           Place the selected analytic flow at the mouse",
+    "Backspace   Remove the most recently placed flow",
+    "Tab         Select the next setting",
+    "PgUp | PgDn Increase or decrease the selected setting",
+    "Arrow Keys  Move around by offsetting generated noise",
+    "O           Reset your offset back to the origin",
+    "R           Reset speed, scale, and offset",
+    "X           Export the current vectors to an SVG",
+    "P           Save the current scene as a preset",
+    "L           Load the most recently saved preset",
+    "H           Show or hide this help screen",
+    "Esc         Quit",
+];
+const HUD_FONT_SIZE: f32 = 20.0;
+const HUD_MARGIN: f32 = 10.0;
+
 pub struct State {
     active_noise_index: Counter,
     active_visualizer_index: Counter,
+    selected_setting: usize,
     params: VisualizerParams,
     visualizer: Box<dyn Visualizer>,
+    /// Drawn over the visualizer to hide what's outside the mask, if there is one.
+    mask_overlay: Option<Texture2D>,
     show_help: bool,
 }
 
 impl State {
     pub fn new() -> Self {
         let params = VisualizerParams::default();
-        let visualizer = Box::new(LineSegments::new(&params));
+        let visualizer = new_visualizer_by_index(0, &params);
 
         Self {
-            // There are nine noise algorithms to choose from
-            active_noise_index: Counter::new(0, 8),
-            active_visualizer_index: Counter::new(0, 1),
+            active_noise_index: Counter::new(0, NOISE_FN_COUNT - 1),
+            active_visualizer_index: Counter::new(0, VISUALIZER_COUNT - 1),
+            selected_setting: 0,
             visualizer,
             params,
+            mask_overlay: None,
             show_help: true,
         }
     }
@@ -43,10 +82,23 @@ impl State {
         if is_key_pressed(KeyCode::N) { self.next_noise() }
         if is_key_pressed(KeyCode::J) { self.previous_visualizer() }
         if is_key_pressed(KeyCode::K) { self.next_visualizer() }
+        if is_key_pressed(KeyCode::Comma) { self.set_seed(self.params.seed.wrapping_sub(1)) }
+        if is_key_pressed(KeyCode::Period) { self.set_seed(self.params.seed.wrapping_add(1)) }
+        if is_key_pressed(KeyCode::S) { self.set_seed(::rand::random()) }
+        if is_key_pressed(KeyCode::W) { self.reroll_warp_offsets() }
+        if is_key_pressed(KeyCode::G) { self.next_field_mode() }
+        if is_key_pressed(KeyCode::F) { self.place_feature() }
+        if is_key_pressed(KeyCode::Backspace) { self.remove_feature() }
+        if is_key_pressed(KeyCode::Tab) { self.selected_setting += 1 }
+        if is_key_pressed(KeyCode::PageUp) { self.nudge_setting(true) }
+        if is_key_pressed(KeyCode::PageDown) { self.nudge_setting(false) }
         if is_key_down(KeyCode::Minus) { self.params.noise_scale += DEFAULT_NOISE_SCALE_INCREMENT }
         if is_key_down(KeyCode::Equal) { self.params.noise_scale = (self.params.noise_scale - DEFAULT_NOISE_SCALE_INCREMENT).max(DEFAULT_NOISE_SCALE / 10.0) }
         if is_key_down(KeyCode::LeftBracket) { self.params.noise_speed = (self.params.noise_speed - DEFAULT_NOISE_SPEED_INCREMENT).max(0.0) }
         if is_key_down(KeyCode::RightBracket) { self.params.noise_speed += DEFAULT_NOISE_SPEED_INCREMENT }
+        if is_key_released(KeyCode::LeftBracket) || is_key_released(KeyCode::RightBracket) { self.resize_loop() }
+        if is_key_pressed(KeyCode::T) { self.toggle_loop() }
+        if is_key_pressed(KeyCode::E) { self.toggle_tiling() }
         if is_key_down(KeyCode::Left) { self.params.base_x_offset -= DEFAULT_MOVE_SPEED }
         if is_key_down(KeyCode::Right) { self.params.base_x_offset += DEFAULT_MOVE_SPEED }
         if is_key_down(KeyCode::Up) { self.params.base_y_offset -= DEFAULT_MOVE_SPEED }
@@ -61,9 +113,12 @@ impl State {
             self.params.base_y_offset = 0.0;
             self.params.noise_speed = DEFAULT_NOISE_SPEED;
             self.params.noise_scale = DEFAULT_NOISE_SCALE;
+            self.resize_loop();
         }
-        if is_key_down(KeyCode::H) { self.show_help = !self.show_help }
+        if is_key_pressed(KeyCode::H) { self.show_help = !self.show_help }
         if is_key_down(KeyCode::X) { self.export_as_svg() }
+        if is_key_pressed(KeyCode::P) { self.save_preset() }
+        if is_key_pressed(KeyCode::L) { self.load_latest_preset() }
         if is_key_down(KeyCode::Escape) { quit() }
 
         let Vec2 { x: mouse_delta_x, y: mouse_delta_y } = mouse_delta_position();
@@ -83,6 +138,134 @@ impl State {
 
     pub fn render(&self) {
         self.visualizer.render();
+
+        if let Some(mask_overlay) = &self.mask_overlay {
+            draw_texture(mask_overlay, 0.0, 0.0, WHITE);
+        }
+
+        if self.show_help {
+            self.render_hud();
+        }
+    }
+
+    fn render_hud(&self) {
+        let mut status = vec![
+            String::new(),
+            match &self.params.noise_graph {
+                Some(noise_graph) => format!("noise: {noise_graph}"),
+                None => format!("noise: {}", NOISE_FN_NAMES[self.active_noise_index.count()]),
+            },
+            format!(
+                "visualizer: {}{}",
+                VISUALIZER_NAMES[self.active_visualizer_index.count()],
+                if self.visualizer.shows_noise_alone() {
+                    " (noise only, no field mode or flows)"
+                } else {
+                    ""
+                }
+            ),
+            format!("seed: {}", self.params.seed),
+            match self.params.loop_phase() {
+                Some(phase) => format!("time: looping, {:.0}% of the way around", phase * 100.0),
+                None => "time: running on".to_owned(),
+            },
+            format!("edges: {}", if self.params.tiled { "wrap around" } else { "stop" }),
+            match (self.params.field_mode, &self.params.formula) {
+                (FieldMode::Formula, Some(formula)) => format!("field: {formula}"),
+                (field_mode, _) => format!("field: {field_mode}"),
+            },
+            match &self.params.mask {
+                Some(mask) => format!("mask: {mask}"),
+                None => "mask: none".to_owned(),
+            },
+        ];
+
+        let selected_setting = self.selected_setting();
+        for setting in self.adjustable_settings() {
+            let marker = if Some(setting) == selected_setting { ">" } else { " " };
+            let (name, value) = match (setting, &self.params.fractal, &self.params.noise_graph) {
+                (Setting::Fractal(field), Some(fractal), _) => (
+                    FractalParams::FIELD_NAMES[field].to_owned(),
+                    fractal.field_value(field),
+                ),
+                (Setting::NoiseGraph(param), _, Some(noise_graph)) => (
+                    noise_graph.param_name(param),
+                    noise_graph.param_value(param),
+                ),
+                (Setting::WarpPreset, _, _) => (
+                    "warp preset".to_owned(),
+                    match self.params.warp.as_ref().and_then(|warp| warp.preset(self.params.seed)) {
+                        Some(preset) => preset.to_string(),
+                        None => "custom".to_owned(),
+                    },
+                ),
+                (Setting::Warp(field), _, _) => (
+                    DomainWarpingNoiseParams::field_name(field),
+                    self.params.warp.as_ref().map_or_else(String::new, |warp| warp.field_value(field)),
+                ),
+                (Setting::Page(field), _, _) => (
+                    PageSetup::FIELD_NAMES[field].to_owned(),
+                    self.params.page.field_value(field),
+                ),
+                (Setting::Plotter(field), _, _) => (
+                    PlotterSettings::FIELD_NAMES[field].to_owned(),
+                    self.params.plotter.field_value(field),
+                ),
+                (Setting::Layers(field), _, _) => (
+                    LayerSettings::FIELD_NAMES[field].to_owned(),
+                    self.params.layers.field_value(field),
+                ),
+                (Setting::Feature(field), _, _) => match self.params.features.last() {
+                    Some(feature) => (
+                        format!("{} {}", feature.kind, FlowFeature::FIELD_NAMES[field]),
+                        feature.field_value(field),
+                    ),
+                    None => (FlowFeature::FIELD_NAMES[field].to_owned(), String::new()),
+                },
+                (Setting::InvertMask, _, _) => (
+                    "invert mask".to_owned(),
+                    self.params.mask.as_ref().map_or_else(String::new, |mask| mask.inverted().to_string()),
+                ),
+                (Setting::LoopPeriod, _, _) => (
+                    "loop period".to_owned(),
+                    match self.params.loop_period {
+                        Some(frames) => format!("{frames} frames"),
+                        None => String::new(),
+                    },
+                ),
+                _ => (
+                    VisualizerSettings::name(setting).to_owned(),
+                    self.params.settings.value(setting),
+                ),
+            };
+            status.push(format!("{marker} {name}: {value}"));
+        }
+
+        let lines: Vec<&str> = HELP_LINES
+            .iter()
+            .copied()
+            .chain(status.iter().map(String::as_str))
+            .collect();
+        // Long lists of settings continue in another column rather than running off the screen
+        let rows = (((screen_height() - HUD_MARGIN * 2.0) / HUD_FONT_SIZE) as usize).max(1);
+        let column_width = HUD_FONT_SIZE * 36.0;
+
+        for (column, column_lines) in lines.chunks(rows).enumerate() {
+            let x = column_width * column as f32;
+
+            draw_rectangle(
+                x,
+                0.0,
+                column_width,
+                HUD_FONT_SIZE * column_lines.len() as f32 + HUD_MARGIN * 2.0,
+                Color::new(0.0, 0.0, 0.0, 0.75),
+            );
+
+            for (i, line) in column_lines.iter().enumerate() {
+                let y = HUD_MARGIN + HUD_FONT_SIZE * (i + 1) as f32;
+                draw_text(line, x + HUD_MARGIN, y, HUD_FONT_SIZE, WHITE);
+            }
+        }
     }
 
     fn next_noise(&mut self) {
@@ -106,20 +289,315 @@ impl State {
     }
 
     fn set_noise_fn(&mut self, index: usize) {
-        self.params.noise_fn = new_noise_fn_by_index(index);
+        if self.params.noise_graph.take().is_some() {
+            info!("no longer using the noise graph");
+        }
+        self.params.warp = DomainWarpingNoiseParams::for_index(index, self.params.seed);
+        self.params.fractal = FractalParams::default_for_noise(index, self.params.warp.as_ref());
+        self.rebuild_noise_fn();
     }
 
-    fn set_visualizer(&mut self, index: usize) {
-        match index {
-            0 => {
-                println!("now using Line Segments visualizer");
-                self.visualizer = Box::new(LineSegments::new(&self.params));
+    /// Rebuild the active noise generator after its seed or settings have changed.
+    fn rebuild_noise_fn(&mut self) {
+        let noise_fn = match &self.params.noise_graph {
+            Some(noise_graph) => noise_graph.build(self.params.seed, self.params.loop_length()),
+            None => new_noise_fn_by_index(
+                self.active_noise_index.count(),
+                self.params.seed,
+                self.params.fractal.as_ref(),
+                self.params.warp.as_ref(),
+                self.params.loop_length(),
+            ),
+        };
+        self.params.set_noise_fn(noise_fn);
+    }
+
+    /// Every setting that affects the current scene: the active noise's fractal, domain warp or
+    /// noise graph settings followed by whatever the active visualizer uses, and then the export
+    /// settings.
+    fn adjustable_settings(&self) -> Vec<Setting> {
+        let mut noise_settings: Vec<Setting> = match (&self.params.noise_graph, self.params.fractal) {
+            (Some(noise_graph), _) => (0..noise_graph.param_count()).map(Setting::NoiseGraph).collect(),
+            (None, Some(_)) => (0..FractalParams::FIELD_NAMES.len()).map(Setting::Fractal).collect(),
+            (None, None) => vec![],
+        };
+
+        if self.params.noise_graph.is_none() && self.params.warp.is_some() {
+            noise_settings.push(Setting::WarpPreset);
+            noise_settings.extend((0..DomainWarpingNoiseParams::FIELD_COUNT).map(Setting::Warp));
+        }
+
+        if self.params.loop_period.is_some() {
+            noise_settings.push(Setting::LoopPeriod);
+        }
+
+        // The last flow placed can be tuned until another one is placed
+        let feature_settings: Vec<Setting> = match self.params.features.last() {
+            Some(feature) => [Setting::FeatureKind]
+                .into_iter()
+                .chain(
+                    (0..FlowFeature::FIELD_NAMES.len())
+                        .filter(|&field| feature.has_field(field))
+                        .map(Setting::Feature),
+                )
+                .chain([Setting::NoiseWeight])
+                .collect(),
+            None => vec![Setting::FeatureKind],
+        };
+        let mask_settings: &[Setting] = match self.params.mask {
+            Some(_) => &[Setting::InvertMask],
+            None => &[],
+        };
+
+        noise_settings
+            .into_iter()
+            .chain(self.visualizer.settings().iter().copied())
+            .chain(feature_settings)
+            .chain(mask_settings.iter().copied())
+            .chain((0..PageSetup::FIELD_NAMES.len()).map(Setting::Page))
+            .chain((0..PlotterSettings::FIELD_NAMES.len()).map(Setting::Plotter))
+            .chain((0..LayerSettings::FIELD_NAMES.len()).map(Setting::Layers))
+            .collect()
+    }
+
+    fn selected_setting(&self) -> Option<Setting> {
+        let settings = self.adjustable_settings();
+
+        match settings.len() {
+            0 => None,
+            len => Some(settings[self.selected_setting % len]),
+        }
+    }
+
+    fn nudge_setting(&mut self, increase: bool) {
+        match self.selected_setting() {
+            Some(Setting::Fractal(field)) => {
+                if let Some(fractal) = &mut self.params.fractal {
+                    fractal.nudge(field, increase);
+                    self.rebuild_noise_fn();
+                }
             }
-            1 => {
-                println!("now using Circles visualizer");
-                self.visualizer = Box::new(Circles::new(&self.params));
+            Some(Setting::NoiseGraph(param)) => {
+                if let Some(noise_graph) = &mut self.params.noise_graph {
+                    noise_graph.nudge_param(param, increase);
+                    self.rebuild_noise_fn();
+                }
+            }
+            Some(Setting::WarpPreset) => {
+                if let Some(warp) = &mut self.params.warp {
+                    // Parameters that have been edited by hand start over from the first preset
+                    let preset = match warp.preset(self.params.seed) {
+                        Some(preset) => preset.cycled(increase),
+                        None => WarpPreset::ALL[0],
+                    };
+                    info!("now using the {preset} domain warp preset");
+                    warp.apply_preset(preset, self.params.seed);
+                    self.rebuild_noise_fn();
+                }
             }
-            _ => unreachable!(),
+            Some(Setting::Warp(field)) => {
+                if let Some(warp) = &mut self.params.warp {
+                    warp.nudge(field, increase);
+                    // A new base starts from its own fractal settings, if it has any
+                    if field == 0 {
+                        self.params.fractal = FractalParams::default_for_index(warp.base());
+                    }
+                    self.rebuild_noise_fn();
+                }
+            }
+            Some(Setting::LoopPeriod) => {
+                if let Some(frames) = self.params.loop_period {
+                    let frames = if increase { frames + 10 } else { frames.saturating_sub(10) };
+                    self.params.loop_period = Some(frames.max(10));
+                    self.rebuild_noise_fn();
+                }
+            }
+            Some(Setting::Page(field)) => self.params.page.nudge(field, increase),
+            Some(Setting::Plotter(field)) => self.params.plotter.nudge(field, increase),
+            Some(Setting::Layers(field)) => self.params.layers.nudge(field, increase),
+            Some(Setting::Feature(field)) => {
+                let mut features = self.params.features.clone();
+                if let Some(feature) = features.last_mut() {
+                    feature.nudge(field, increase);
+                    self.params.set_features(features);
+                }
+            }
+            Some(Setting::InvertMask) => {
+                if let Some(mask) = &mut self.params.mask {
+                    mask.set_inverted(!mask.inverted());
+                    self.rebuild_mask_overlay();
+                }
+            }
+            Some(setting) => {
+                let mm_per_px = self.params.page.mm_per_px();
+                self.params.settings.nudge(setting, increase, mm_per_px)
+            }
+            None => {}
+        }
+    }
+
+    fn next_field_mode(&mut self) {
+        let mut field_mode = self.params.field_mode.cycled(true);
+        // There's nothing to follow in formula mode until a formula has been loaded
+        if field_mode == FieldMode::Formula && self.params.formula.is_none() {
+            field_mode = field_mode.cycled(true);
+        }
+        self.params.set_field_mode(field_mode);
+        info!("now using {} field mode", self.params.field_mode);
+    }
+
+    fn place_feature(&mut self) {
+        let (x, y) = mouse_position();
+        let feature = FlowFeature {
+            kind: self.params.settings.feature_kind,
+            position: [x as f64, y as f64],
+            ..FlowFeature::default()
+        };
+        info!("placing a {} at ({x}, {y})", feature.kind);
+
+        let mut features = self.params.features.clone();
+        features.push(feature);
+        self.params.set_features(features);
+    }
+
+    fn remove_feature(&mut self) {
+        let mut features = self.params.features.clone();
+        if let Some(feature) = features.pop() {
+            info!("removing the {} at {:?}", feature.kind, feature.position);
+            self.params.set_features(features);
+        }
+    }
+
+    fn set_seed(&mut self, seed: u32) {
+        info!("now using seed {seed}");
+        // Random warp parameters follow the seed, but ones that were picked by hand are kept
+        if let Some(warp) = &mut self.params.warp
+            && warp.preset(self.params.seed) == Some(WarpPreset::Random)
+        {
+            warp.apply_preset(WarpPreset::Random, seed);
+        }
+        self.params.seed = seed;
+        self.rebuild_noise_fn();
+    }
+
+    /// Make the loop as long as time moves over one period at the current speed. This only
+    /// happens once the speed settles, so holding [ or ] doesn't rebuild the noise every frame.
+    fn resize_loop(&mut self) {
+        if self.params.loop_period.is_some() {
+            self.rebuild_noise_fn();
+        }
+    }
+
+    fn toggle_loop(&mut self) {
+        self.params.loop_period = match self.params.loop_period {
+            Some(_) => None,
+            None => Some(DEFAULT_LOOP_PERIOD),
+        };
+        match self.params.loop_period {
+            Some(frames) => info!("looping time every {frames} frames"),
+            None => info!("letting time run on"),
+        }
+        self.rebuild_noise_fn();
+    }
+
+    fn toggle_tiling(&mut self) {
+        self.params.tiled = !self.params.tiled;
+        if self.params.tiled {
+            info!("wrapping the field around the edges");
+        } else {
+            info!("no longer wrapping the field around the edges");
+        }
+    }
+
+    fn reroll_warp_offsets(&mut self) {
+        let Some(warp) = &mut self.params.warp else {
+            info!("only domain warping noise has warp offsets to re-roll");
+            return;
+        };
+
+        warp.reroll_offsets(::rand::random());
+        info!("re-rolled the domain warp offsets");
+        self.rebuild_noise_fn();
+    }
+
+    fn set_visualizer(&mut self, index: usize) {
+        self.visualizer = new_visualizer_by_index(index, &self.params);
+    }
+
+    pub fn preset(&self) -> Preset {
+        Preset {
+            noise: self.active_noise_index.count(),
+            visualizer: self.active_visualizer_index.count(),
+            seed: self.params.seed,
+            x_offset: self.params.base_x_offset,
+            y_offset: self.params.base_y_offset,
+            z_offset: self.params.z_offset,
+            scale: self.params.noise_scale,
+            speed: self.params.noise_speed,
+            loop_period: self.params.loop_period,
+            tiled: self.params.tiled,
+            page: self.params.page,
+            plotter: self.params.plotter.clone(),
+            layers: self.params.layers.clone(),
+            mask: self.params.mask.clone(),
+            fractal: self.params.fractal,
+            warp: self.params.warp.clone(),
+            noise_graph: self.params.noise_graph.clone(),
+            settings: self.params.settings.clone(),
+            field_mode: self.params.field_mode,
+            formula: self.params.formula.clone(),
+            features: self.params.features.clone(),
+        }
+    }
+
+    pub fn apply_preset(&mut self, preset: &Preset) {
+        self.active_noise_index.set_count(preset.noise);
+        self.active_visualizer_index.set_count(preset.visualizer);
+        self.params = preset.to_params();
+        self.set_visualizer(preset.visualizer);
+        self.rebuild_mask_overlay();
+    }
+
+    fn rebuild_mask_overlay(&mut self) {
+        // Outside the mask is covered in the colour the screen is cleared to
+        self.mask_overlay = self
+            .params
+            .mask
+            .as_ref()
+            .map(|mask| Texture2D::from_image(&mask.overlay(BLACK)));
+    }
+
+    fn save_preset(&self) {
+        let Ok(base_path) = std::env::var("PRESET_DIRECTORY") else {
+            error!("saving preset failed: PRESET_DIRECTORY must be set to a valid directory in order to save a preset");
+            return;
+        };
+
+        let preset_filepath = unused_filepath(base_path, self.params.seed, "toml");
+
+        match self.preset().save(&preset_filepath) {
+            Ok(()) => info!("preset saved to {}", preset_filepath.to_string_lossy()),
+            Err(err) => error!("saving preset failed: {err}"),
+        }
+    }
+
+    fn load_latest_preset(&mut self) {
+        let Ok(base_path) = std::env::var("PRESET_DIRECTORY") else {
+            error!("loading preset failed: PRESET_DIRECTORY must be set to a valid directory in order to load a preset");
+            return;
+        };
+
+        let Some(preset_filepath) = latest_preset_filepath(base_path) else {
+            error!("loading preset failed: no presets found in PRESET_DIRECTORY");
+            return;
+        };
+
+        match Preset::load(&preset_filepath) {
+            Ok(preset) => {
+                info!("loaded preset from {}", preset_filepath.to_string_lossy());
+                self.apply_preset(&preset);
+            }
+            Err(err) => error!("loading preset failed: {err}"),
         }
     }
 
@@ -134,39 +612,14 @@ impl State {
 
         let base_path = base_path.unwrap();
 
-        let document = self.visualizer.build_svg_document_from_state();
-        let current_date = Local::today().format("%Y-%m-%d");
-        let svg_filename = format!("{}-vector-field-visualization.svg", &current_date);
-        let mut svg_filepath: PathBuf = [base_path, svg_filename].iter().collect();
-
-        // I don't want to silently overwrite anything so I look for an unused filename,
-        // incrementing the counter until I find an unused number
-        // I could have also used a random string/number, I just like this better
-        if svg_filepath.exists() {
-            let mut counter = 1;
-
-            while svg_filepath.exists() {
-                if counter > 100 {
-                    warn!(
-                        "export_as_svg counter has reached {}, you're not in an infinite loop are you?",
-                        counter
-                    );
-                }
+        let svg_filepath = unused_svg_filepath(base_path, self.params.seed);
 
-                let _ = svg_filepath.pop();
-                let svg_filename = format!(
-                    "{}-vector-field-visualization-{}.svg",
-                    &current_date, &counter
-                );
-                svg_filepath.push(svg_filename);
-                counter += 1;
-            }
+        match save_export(self.visualizer.as_ref(), &self.params, &svg_filepath) {
+            Ok(()) => info!(
+                "SVG successfully exported to {}",
+                &svg_filepath.to_string_lossy()
+            ),
+            Err(err) => error!("SVG export failed: {err}"),
         }
-
-        svg::save(&svg_filepath, &document).expect("couldn't save SVG");
-        info!(
-            "SVG successfully exported to {}",
-            &svg_filepath.to_string_lossy()
-        );
     }
 }
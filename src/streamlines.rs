@@ -0,0 +1,305 @@
+use crate::{
+    consts::{
+        GRID_CELL_H, GRID_CELL_W, GRID_SIZE_X, GRID_SIZE_Y, SCREEN_H, SCREEN_W,
+        STREAMLINE_MAX_LENGTH, STREAMLINE_SEED_SPACING, STREAMLINE_STEP, VECTOR_WIDTH,
+    },
+    visualizer::{direction, Visualizer, VisualizerParams},
+};
+use log::info;
+use macroquad::prelude::*;
+use svg::node::element::{self, path::Data};
+
+type Point2<T> = [T; 2];
+
+/// How a streamline takes each step through the field.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Integrator {
+    /// Follow the direction at the current point. Fast, but drifts on tight curves.
+    Euler,
+    /// Fourth-order Runge-Kutta. Four samples per step, but follows curves closely.
+    Rk4,
+}
+
+impl Integrator {
+    /// Take a single step of `step` pixels from `p`, or `None` if the field is too weak to move.
+    pub fn step(
+        self,
+        params: &VisualizerParams,
+        p: Point2<f64>,
+        step: f64,
+    ) -> Option<Point2<f64>> {
+        let [vx, vy] = params.vector_at(p);
+        if vx.hypot(vy) < STALL_THRESHOLD {
+            return None;
+        }
+        let k1 = direction([vx, vy])?;
+
+        let [dx, dy] = match self {
+            Integrator::Euler => k1,
+            Integrator::Rk4 => {
+                let half = step / 2.0;
+                let direction = |p: Point2<f64>| params.direction_at(p);
+                let k2 = direction([p[0] + k1[0] * half, p[1] + k1[1] * half])?;
+                let k3 = direction([p[0] + k2[0] * half, p[1] + k2[1] * half])?;
+                let k4 = direction([p[0] + k3[0] * step, p[1] + k3[1] * step])?;
+
+                [
+                    (k1[0] + 2.0 * k2[0] + 2.0 * k3[0] + k4[0]) / 6.0,
+                    (k1[1] + 2.0 * k2[1] + 2.0 * k3[1] + k4[1]) / 6.0,
+                ]
+            }
+        };
+
+        // The RK4 samples can cancel each other out across sinks and saddles, even where the
+        // field is strong
+        if dx.hypot(dy) < CANCELLED_STEP {
+            return None;
+        }
+
+        Some([p[0] + dx * step, p[1] + dy * step])
+    }
+}
+
+/// Fields weaker than this count as stalled, so that lines stop where the field dies away at
+/// sinks, sources and saddles rather than creeping around them. The angle field mode is as strong
+/// as the noise weight everywhere, and the weight goes down in steps of 0.1, so this only stops
+/// angle field lines once the weight is 0 and only the flows are left.
+const STALL_THRESHOLD: f64 = 0.01;
+
+/// RK4 steps shorter than this, as a fraction of a full step, count as stalled too.
+const CANCELLED_STEP: f64 = 0.1;
+
+/// Trace a line from `seed` in one direction, stopping at the edges of the screen, once it's
+/// `max_length` pixels long, when the field stalls or turns back on itself, or when `is_clear`
+/// rejects the next point. On a tiled canvas, lines don't stop at the edges and `is_clear` is
+/// given points wrapped back onto the canvas.
+pub fn trace_streamline(
+    params: &VisualizerParams,
+    integrator: Integrator,
+    seed: Point2<f64>,
+    step: f64,
+    max_length: f64,
+    is_clear: &mut impl FnMut(Point2<f64>) -> bool,
+) -> Vec<Point2<f64>> {
+    let mut points = vec![seed];
+    let mut length = 0.0;
+    let mut previous_direction: Option<Point2<f64>> = None;
+
+    while length < max_length {
+        let p = *points.last().unwrap();
+        let Some(next) = integrator.step(params, p, step) else {
+            break;
+        };
+
+        // Lines on a tiled canvas carry on past the edges, to be cut up by `wrap_polyline`
+        if !(params.tiled || is_on_screen(next)) || !is_clear(params.wrap(next)) {
+            break;
+        }
+
+        let direction = [next[0] - p[0], next[1] - p[1]];
+        // A line that doubles back on itself has run into a sink
+        if let Some([px, py]) = previous_direction
+            && px * direction[0] + py * direction[1] < 0.0
+        {
+            break;
+        }
+
+        previous_direction = Some(direction);
+        length += direction[0].hypot(direction[1]);
+        points.push(next);
+    }
+
+    points
+}
+
+/// Trace a line both ways from `seed` so that the seed ends up in the middle of the line.
+pub fn trace_streamline_both_ways(
+    params: &VisualizerParams,
+    integrator: Integrator,
+    seed: Point2<f64>,
+    step: f64,
+    max_length: f64,
+    is_clear: &mut impl FnMut(Point2<f64>) -> bool,
+) -> Vec<Point2<f64>> {
+    let mut backward =
+        trace_streamline(params, integrator, seed, -step, max_length / 2.0, is_clear);
+    let forward = trace_streamline(params, integrator, seed, step, max_length / 2.0, is_clear);
+
+    backward.reverse();
+    backward.extend_from_slice(&forward[1..]);
+    backward
+}
+
+pub fn is_on_screen([x, y]: Point2<f64>) -> bool {
+    (0.0..=SCREEN_W as f64).contains(&x) && (0.0..=SCREEN_H as f64).contains(&y)
+}
+
+/// Cut a line that wanders off the edges of a tiled canvas into pieces that each lie on the canvas,
+/// moved back by whole canvas widths and heights. The pieces end exactly on the edges, so copies
+/// of the canvas placed side by side join up.
+pub fn wrap_polyline(points: &[Point2<f64>]) -> Vec<Vec<Point2<f64>>> {
+    let size = [SCREEN_W as f64, SCREEN_H as f64];
+    let mut pieces = Vec::new();
+    let mut piece: Vec<Point2<f64>> = Vec::new();
+    let mut piece_tile = None;
+
+    for pair in points.windows(2) {
+        let [a, b] = [pair[0], pair[1]];
+        let at = |t: f64| match t {
+            0.0 => a,
+            1.0 => b,
+            t => [a[0] + (b[0] - a[0]) * t, a[1] + (b[1] - a[1]) * t],
+        };
+
+        // Where the segment crosses the edges of any tile, as fractions of the way from a to b
+        let mut cuts = vec![0.0, 1.0];
+        for axis in 0..2 {
+            let (low, high) = (a[axis].min(b[axis]), a[axis].max(b[axis]));
+            let mut edge = (low / size[axis]).floor() + 1.0;
+            while edge * size[axis] < high {
+                cuts.push((edge * size[axis] - a[axis]) / (b[axis] - a[axis]));
+                edge += 1.0;
+            }
+        }
+        cuts.sort_by(f64::total_cmp);
+
+        for span in cuts.windows(2) {
+            let [t0, t1] = [span[0], span[1]];
+            if t1 <= t0 {
+                continue;
+            }
+
+            let middle = at((t0 + t1) / 2.0);
+            let tile = [
+                (middle[0] / size[0]).floor(),
+                (middle[1] / size[1]).floor(),
+            ];
+            let shift = |[x, y]: Point2<f64>| [x - tile[0] * size[0], y - tile[1] * size[1]];
+
+            if piece_tile != Some(tile) {
+                if piece.len() > 1 {
+                    pieces.push(std::mem::take(&mut piece));
+                }
+                piece = vec![shift(at(t0))];
+                piece_tile = Some(tile);
+            }
+            piece.push(shift(at(t1)));
+        }
+    }
+
+    if piece.len() > 1 {
+        pieces.push(piece);
+    }
+
+    pieces
+}
+
+/// Build an SVG path that visits each point in order.
+pub fn polyline_to_path(points: &[Point2<f64>]) -> element::Path {
+    let mut data = Data::new();
+
+    for (i, &[x, y]) in points.iter().enumerate() {
+        data = if i == 0 {
+            data.move_to((x, y))
+        } else {
+            data.line_to((x, y))
+        };
+    }
+
+    element::Path::new().set("d", data)
+}
+
+/// Build an SVG with one `<path>` per polyline, framed like the other visualizers' exports.
+pub fn build_svg_document_from_polylines(polylines: &[Vec<Point2<f64>>]) -> svg::Document {
+    let doc = svg::Document::new().set("viewBox", (0, 0, SCREEN_W, SCREEN_H));
+
+    let mut group = element::Group::new()
+        .set("fill", "none")
+        .set("stroke", "black")
+        .set("stroke-width", "0.3mm");
+
+    info!("rendering {} paths", polylines.len());
+
+    for polyline in polylines.iter() {
+        group = group.add(polyline_to_path(polyline));
+    }
+
+    let bounding_rect = element::Rectangle::new()
+        .set("width", SCREEN_W)
+        .set("height", SCREEN_H)
+        .set("fill", "none")
+        .set("stroke", "black")
+        .set("stroke-width", "1mm");
+
+    doc.add(group).add(bounding_rect)
+}
+
+pub fn draw_polyline(points: &[Point2<f64>], color: Color) {
+    for pair in points.windows(2) {
+        let [[x1, y1], [x2, y2]] = [pair[0], pair[1]];
+        draw_line(x1 as f32, y1 as f32, x2 as f32, y2 as f32, VECTOR_WIDTH as f32, color);
+    }
+}
+
+/// Flow lines traced through the angle field from a regular grid of seed points.
+pub struct Streamlines {
+    integrator: Integrator,
+    streamlines: Vec<Vec<Point2<f64>>>,
+}
+
+impl Streamlines {
+    pub fn new(params: &VisualizerParams, integrator: Integrator) -> Self {
+        let mut streamlines = Self {
+            integrator,
+            streamlines: Vec::new(),
+        };
+        streamlines.trace(params);
+
+        streamlines
+    }
+
+    fn trace(&mut self, params: &VisualizerParams) {
+        self.streamlines.clear();
+
+        for y in (0..GRID_SIZE_Y).step_by(STREAMLINE_SEED_SPACING) {
+            for x in (0..GRID_SIZE_X).step_by(STREAMLINE_SEED_SPACING) {
+                let seed = [
+                    x as f64 * GRID_CELL_W + GRID_CELL_W / 2.0,
+                    y as f64 * GRID_CELL_H + GRID_CELL_H / 2.0,
+                ];
+                let streamline = trace_streamline_both_ways(
+                    params,
+                    self.integrator,
+                    seed,
+                    STREAMLINE_STEP,
+                    STREAMLINE_MAX_LENGTH,
+                    &mut |_| true,
+                );
+
+                if params.tiled {
+                    self.streamlines.extend(wrap_polyline(&streamline));
+                } else if streamline.len() > 1 {
+                    self.streamlines.push(streamline);
+                }
+            }
+        }
+    }
+}
+
+impl Visualizer for Streamlines {
+    fn update(&mut self, params: &mut VisualizerParams) {
+        self.trace(params);
+        // The grid visualizers move through time once per row, so keep pace with them
+        params.z_offset += params.noise_speed * GRID_SIZE_Y as f64;
+    }
+
+    fn build_svg_document_from_state(&self) -> svg::Document {
+        build_svg_document_from_polylines(&self.streamlines)
+    }
+
+    fn render(&self) {
+        for streamline in self.streamlines.iter() {
+            draw_polyline(streamline, WHITE);
+        }
+    }
+}
@@ -1,35 +1,331 @@
 use crate::{
-    consts::{DEFAULT_NOISE_SCALE, DEFAULT_NOISE_SPEED},
-    noise::new_noise_fn_by_index,
+    arrows::Arrows,
+    circles::Circles,
+    contours::Contours,
+    consts::{
+        DEFAULT_NOISE_SCALE, DEFAULT_NOISE_SPEED, GRADIENT_STEP, GRID_CELL_H, GRID_CELL_W,
+        GRID_SIZE_Y, SCREEN_H, SCREEN_W,
+    },
+    evenly_spaced_streamlines::EvenlySpacedStreamlines,
+    field::{
+        features_field, CompositeField, FieldMode, FlowFeature, Formula, SharedNoiseFn, VectorField,
+    },
+    heatmap::Heatmap,
+    layers::LayerSettings,
+    line_segments::LineSegments,
+    mask::Mask,
+    noise::{new_noise_fn_by_index, DomainWarpingNoiseParams, FractalParams, NoiseGraph},
+    page::PageSetup,
+    plotter::PlotterSettings,
+    particles::Particles,
+    settings::{Setting, VisualizerSettings},
+    streamlines::{Integrator, Streamlines},
 };
 use noise::NoiseFn;
+use std::rc::Rc;
 
 pub struct VisualizerParams {
     pub base_x_offset: f64,
     pub base_y_offset: f64,
     pub noise_scale: f64,
     pub noise_speed: f64,
-    pub noise_fn: Box<dyn NoiseFn<f64, 3>>,
+    /// Change this with `set_noise_fn` so that `field` follows it.
+    pub noise_fn: SharedNoiseFn,
+    /// The vector field derived from `noise_fn` according to `field_mode`.
+    pub field: Box<dyn VectorField>,
+    /// The field used by `FieldMode::Formula`.
+    pub formula: Option<Formula>,
+    /// Analytic flows placed on the canvas and added to `field`. Change these with `set_features`
+    /// so that `feature_field` follows them.
+    pub features: Vec<FlowFeature>,
+    /// The sum of `features`, sampled in canvas pixels.
+    pub feature_field: CompositeField,
+    /// The seed used to build `noise_fn`. Keep them in sync so that exports can be reproduced.
+    pub seed: u32,
+    /// The fractal settings used to build `noise_fn`, or `None` if it isn't a fractal noise.
+    pub fractal: Option<FractalParams>,
+    /// The warp used to build `noise_fn`, or `None` if it isn't domain warping noise.
+    pub warp: Option<DomainWarpingNoiseParams>,
+    /// Used to build `noise_fn` instead of the active noise generator when it's set.
+    pub noise_graph: Option<NoiseGraph>,
+    pub settings: VisualizerSettings,
+    pub field_mode: FieldMode,
     pub z_offset: f64,
+    /// How many frames it takes time to loop back around, or `None` if it goes on forever.
+    /// `noise_fn` has to be built for looping with `loop_length` whenever this or the speed changes.
+    pub loop_period: Option<u32>,
+    /// Whether the field wraps around at the edges of the canvas so that it tiles seamlessly.
+    pub tiled: bool,
+    /// How exports are laid out on paper.
+    pub page: PageSetup,
+    /// Which plotter files are exported along with SVGs, and how they drive the plotter.
+    pub plotter: PlotterSettings,
+    /// How exports are split between pens.
+    pub layers: LayerSettings,
+    /// The part of the canvas the visualization is limited to, or `None` to use all of it.
+    pub mask: Option<Mask>,
 }
 
 impl Default for VisualizerParams {
     fn default() -> Self {
-        let noise_fn = new_noise_fn_by_index(0);
+        let seed = 0;
+        let warp = DomainWarpingNoiseParams::for_index(0, seed);
+        let fractal = FractalParams::default_for_noise(0, warp.as_ref());
+        let noise_fn: SharedNoiseFn =
+            Rc::from(new_noise_fn_by_index(0, seed, fractal.as_ref(), warp.as_ref(), None));
+        let field_mode = FieldMode::default();
 
         Self {
             base_x_offset: 0.0,
             base_y_offset: 0.0,
             noise_scale: DEFAULT_NOISE_SCALE,
             noise_speed: DEFAULT_NOISE_SPEED,
+            field: field_mode.field(noise_fn.clone(), None, seed, None),
+            formula: None,
+            features: Vec::new(),
+            feature_field: CompositeField::new(),
             noise_fn,
+            seed,
+            fractal,
+            warp,
+            noise_graph: None,
+            settings: VisualizerSettings::default(),
+            field_mode,
             z_offset: 0.0,
+            loop_period: None,
+            tiled: false,
+            page: PageSetup::default(),
+            plotter: PlotterSettings::default(),
+            layers: LayerSettings::default(),
+            mask: None,
         }
     }
 }
 
+impl VisualizerParams {
+    /// Replace the noise and rebuild the field derived from it.
+    pub fn set_noise_fn(&mut self, noise_fn: Box<dyn NoiseFn<f64, 3>>) {
+        self.noise_fn = Rc::from(noise_fn);
+        self.rebuild_field();
+    }
+
+    /// Change how the noise is turned into a field and rebuild the field.
+    pub fn set_field_mode(&mut self, field_mode: FieldMode) {
+        self.field_mode = field_mode;
+        self.rebuild_field();
+    }
+
+    fn rebuild_field(&mut self) {
+        self.field = self.field_mode.field(
+            self.noise_fn.clone(),
+            self.formula.as_ref(),
+            self.seed,
+            self.loop_length(),
+        );
+    }
+
+    /// Replace the analytic flows and rebuild their sum.
+    pub fn set_features(&mut self, features: Vec<FlowFeature>) {
+        self.feature_field = features_field(&features);
+        self.features = features;
+    }
+
+    /// How far time moves through the noise over one loop, or `None` if it doesn't loop.
+    pub fn loop_length(&self) -> Option<f64> {
+        loop_length(self.loop_period, self.noise_speed)
+    }
+
+    /// How far through the loop the current frame is, from 0 up to 1.
+    pub fn loop_phase(&self) -> Option<f64> {
+        let loop_period = self.loop_period?;
+
+        // Time stands still at a speed of 0
+        if self.noise_speed <= 0.0 {
+            return Some(0.0);
+        }
+
+        // Count whole steps of `noise_speed` rather than dividing `z_offset` directly, so that
+        // rounding errors in `z_offset` can't keep a loop from coming back exactly
+        let steps_per_loop = loop_period as i64 * GRID_SIZE_Y as i64;
+        let steps = (self.z_offset / self.noise_speed).round() as i64;
+
+        Some(steps.rem_euclid(steps_per_loop) as f64 / steps_per_loop as f64)
+    }
+
+    /// The time coordinate of the noise: the loop phase when looping, otherwise `z_offset`.
+    pub fn time(&self) -> f64 {
+        self.loop_phase().unwrap_or(self.z_offset)
+    }
+
+    /// Where a point on the screen lands in noise space. The top left corner of grid cell
+    /// `(x, y)` lands `(x, y) * noise_scale` past the base offsets, which is where the grid based
+    /// visualizers sample each cell, half a cell up and to the left of the centre they draw it at.
+    fn to_noise_space(&self, [x, y]: [f64; 2]) -> [f64; 2] {
+        [
+            self.base_x_offset + x / GRID_CELL_W * self.noise_scale,
+            self.base_y_offset + y / GRID_CELL_H * self.noise_scale,
+        ]
+    }
+
+    /// Move a point that's off the edge of a tiled canvas back onto it. Points are left alone
+    /// when the canvas isn't tiled.
+    pub fn wrap(&self, [x, y]: [f64; 2]) -> [f64; 2] {
+        if !self.tiled {
+            return [x, y];
+        }
+
+        [x.rem_euclid(SCREEN_W as f64), y.rem_euclid(SCREEN_H as f64)]
+    }
+
+    /// When the canvas is tiled, blend samples from the four canvas sized tiles around `p` so
+    /// that the result wraps seamlessly at the edges, the same way the noise crate builds
+    /// seamless maps. Only the noise is blended; see `features_at`.
+    fn tile<const N: usize>(&self, p: [f64; 2], sample: impl Fn([f64; 2]) -> [f64; N]) -> [f64; N] {
+        if !self.tiled {
+            return sample(p);
+        }
+
+        let (w, h) = (SCREEN_W as f64, SCREEN_H as f64);
+        let [x, y] = self.wrap(p);
+        let (u, v) = (x / w, y / h);
+        let corners = [
+            (sample([x, y]), (1.0 - u) * (1.0 - v)),
+            (sample([x - w, y]), u * (1.0 - v)),
+            (sample([x, y - h]), (1.0 - u) * v),
+            (sample([x - w, y - h]), u * v),
+        ];
+
+        let mut blended = [0.0; N];
+        for (values, weight) in corners {
+            for (blended, value) in blended.iter_mut().zip(values) {
+                *blended += weight * value;
+            }
+        }
+
+        blended
+    }
+
+    /// Sample the noise at a point on the screen.
+    pub fn noise_at(&self, p: [f64; 2]) -> f64 {
+        let time = self.time();
+        let [noise] = self.tile(p, |p| {
+            let [x, y] = self.to_noise_space(p);
+            [self.noise_fn.get([x, y, time])]
+        });
+
+        noise
+    }
+
+    /// Sample the vector field at a point on the screen, with the analytic flows added on top.
+    pub fn vector_at(&self, p: [f64; 2]) -> [f64; 2] {
+        let time = self.time();
+        let weight = self.settings.noise_weight;
+
+        let [dx, dy] = self.tile(p, |p| {
+            let [x, y] = self.to_noise_space(p);
+            self.field.sample(x, y, time)
+        });
+        let [fx, fy] = self.features_at(p, time);
+
+        [weight * dx + fx, weight * dy + fy]
+    }
+
+    /// Sample the analytic flows at a point on the screen. On a tiled canvas each flow is
+    /// measured from its nearest copy, so that it keeps its full strength wherever it's placed.
+    fn features_at(&self, [x, y]: [f64; 2], time: f64) -> [f64; 2] {
+        if !self.tiled {
+            return self.feature_field.sample(x, y, time);
+        }
+
+        let size = [SCREEN_W as f64, SCREEN_H as f64];
+        self.features
+            .iter()
+            .map(|feature| feature.sample_wrapped(x, y, size))
+            .fold([0.0, 0.0], |[ax, ay], [bx, by]| [ax + bx, ay + by])
+    }
+
+    /// The rate of change of the noise at a point on the screen, per pixel, found with central
+    /// differences.
+    pub fn gradient_at(&self, [x, y]: [f64; 2]) -> [f64; 2] {
+        let h = GRADIENT_STEP;
+        let dx = self.noise_at([x + h, y]) - self.noise_at([x - h, y]);
+        let dy = self.noise_at([x, y + h]) - self.noise_at([x, y - h]);
+
+        [dx / (2.0 * h), dy / (2.0 * h)]
+    }
+
+    /// Which way the field points at a point on the screen, as a unit vector. `None` means the
+    /// field is too weak there to have a direction, or isn't a number at all.
+    pub fn direction_at(&self, p: [f64; 2]) -> Option<[f64; 2]> {
+        direction(self.vector_at(p))
+    }
+}
+
+/// A vector scaled to unit length, or `None` if it's too short to have a direction or isn't a
+/// number at all.
+pub fn direction([dx, dy]: [f64; 2]) -> Option<[f64; 2]> {
+    let length = dx.hypot(dy);
+    if !length.is_finite() || length < FLAT_FIELD {
+        return None;
+    }
+
+    Some([dx / length, dy / length])
+}
+
+/// Vectors shorter than this are treated as having no direction.
+const FLAT_FIELD: f64 = 1e-12;
+
+/// How far time moves through the noise over `loop_period` frames. Every visualizer moves
+/// `z_offset` on by `noise_speed` once per row of the grid each frame.
+pub fn loop_length(loop_period: Option<u32>, noise_speed: f64) -> Option<f64> {
+    loop_period.map(|frames| frames as f64 * noise_speed * GRID_SIZE_Y as f64)
+}
+
 pub trait Visualizer {
     fn update(&mut self, params: &mut VisualizerParams);
     fn build_svg_document_from_state(&self) -> svg::Document;
     fn render(&self);
+
+    /// The settings this visualizer reads from `VisualizerParams::settings`.
+    fn settings(&self) -> &'static [Setting] {
+        &[]
+    }
+
+    /// Whether this visualizer draws the noise itself rather than the field made from it, so that
+    /// the field mode and the analytic flows don't change it.
+    fn shows_noise_alone(&self) -> bool {
+        false
+    }
+}
+
+/// How many visualizers `new_visualizer_by_index` knows about.
+pub const VISUALIZER_COUNT: usize = 9;
+
+pub const VISUALIZER_NAMES: [&str; VISUALIZER_COUNT] = [
+    "Line Segments",
+    "Circles",
+    "Streamlines (Euler)",
+    "Streamlines (RK4)",
+    "Evenly Spaced Streamlines",
+    "Particles",
+    "Arrows",
+    "Heatmap",
+    "Contours",
+];
+
+pub fn new_visualizer_by_index(index: usize, params: &VisualizerParams) -> Box<dyn Visualizer> {
+    log::info!("now using {} visualizer", VISUALIZER_NAMES[index]);
+
+    match index {
+        0 => Box::new(LineSegments::new(params)),
+        1 => Box::new(Circles::new(params)),
+        2 => Box::new(Streamlines::new(params, Integrator::Euler)),
+        3 => Box::new(Streamlines::new(params, Integrator::Rk4)),
+        4 => Box::new(EvenlySpacedStreamlines::new(params)),
+        5 => Box::new(Particles::new(params)),
+        6 => Box::new(Arrows::new(params)),
+        7 => Box::new(Heatmap::new(params)),
+        8 => Box::new(Contours::new(params)),
+        _ => unreachable!(),
+    }
 }